@@ -0,0 +1,154 @@
+use rand::{seq::SliceRandom, thread_rng};
+
+use crate::{
+    board::GameResult,
+    history::{History, Turn},
+    pieces::*,
+    variant::movable_pieces,
+};
+
+/// Taking the king ends the game, so it outweighs everything else
+const KING_VALUE: i32 = 10_000;
+
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::King => KING_VALUE,
+        PieceType::Queen => 9,
+        PieceType::Rook => 5,
+        PieceType::Bishop => 3,
+        PieceType::Knight => 3,
+        PieceType::Pawn => 1,
+    }
+}
+
+/// Material balance from the point of view of `color`
+fn evaluate(pieces: &[Piece], color: PieceColor) -> i32 {
+    pieces
+        .iter()
+        .map(|piece| {
+            let value = piece_value(piece.piece_type);
+            if piece.color == color {
+                value
+            } else {
+                -value
+            }
+        })
+        .sum()
+}
+
+/// Every move `color` can make, drops included, with captures first so they get searched early
+fn all_moves(
+    pieces: &[Piece],
+    history: &History,
+    color: PieceColor,
+) -> Vec<((u8, u8), MovePosition)> {
+    let rules = history.rules();
+    let mut moves: Vec<_> = movable_pieces(rules, pieces, history, color)
+        .into_iter()
+        .flat_map(|piece| {
+            rules
+                .moves(&piece, pieces, history)
+                .into_iter()
+                .map(move |mv| ((piece.x, piece.y), mv))
+        })
+        .collect();
+    moves.sort_by_key(|(_, (_, _, takeable))| takeable.is_none());
+    moves
+}
+
+/// Plays the move on a copy of the position and the history
+fn play(
+    pieces: &[Piece],
+    history: &History,
+    color: PieceColor,
+    from: (u8, u8),
+    mv: MovePosition,
+) -> (Vec<Piece>, History) {
+    let mut pieces = pieces.to_vec();
+    let piece = history
+        .piece_on(&pieces, from)
+        .filter(|piece| piece.color == color)
+        .expect("move without a piece");
+    let removed = history.rules().play(&mut pieces, from, mv, None);
+    let turn = Turn::new(&piece, (mv.0, mv.1), &removed, &pieces, history);
+    let mut history = history.clone();
+    history.turns.push(turn);
+    (pieces, history)
+}
+
+/// Score of a finished game for `color`, preferring to win sooner and to lose later
+fn result_score(result: &GameResult, color: PieceColor, depth: u8) -> i32 {
+    match result.winner {
+        Some(winner) if winner == color => KING_VALUE * 2 + depth as i32,
+        Some(_) => -(KING_VALUE * 2 + depth as i32),
+        None => 0,
+    }
+}
+
+fn negamax(
+    pieces: &[Piece],
+    history: &History,
+    color: PieceColor,
+    depth: u8,
+    mut alpha: i32,
+    beta: i32,
+) -> i32 {
+    if let Some(result) = history.rules().result(pieces, history, color) {
+        return result_score(&result, color, depth);
+    }
+    if depth == 0 {
+        return evaluate(pieces, color);
+    }
+    let moves = all_moves(pieces, history, color);
+    if moves.is_empty() {
+        return evaluate(pieces, color);
+    }
+    let mut best = -i32::MAX;
+    for (from, mv) in moves {
+        let (pieces, history) = play(pieces, history, color, from, mv);
+        let score = -negamax(
+            &pieces,
+            &history,
+            color.opposite(),
+            depth - 1,
+            -beta,
+            -alpha,
+        );
+        best = best.max(score);
+        alpha = alpha.max(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+/// Picks a move for `color` searching `depth` plies, choosing randomly between equally good moves
+pub fn best_move(
+    pieces: &[Piece],
+    history: &History,
+    color: PieceColor,
+    depth: u8,
+) -> Option<((u8, u8), (u8, u8))> {
+    let depth = depth.max(1);
+    let mut scored = Vec::new();
+    for (from, mv) in all_moves(pieces, history, color) {
+        let (next, next_history) = play(pieces, history, color, from, mv);
+        let score = -negamax(
+            &next,
+            &next_history,
+            color.opposite(),
+            depth - 1,
+            -i32::MAX,
+            i32::MAX,
+        );
+        scored.push((score, (from, (mv.0, mv.1))));
+    }
+    let best_score = scored.iter().map(|(score, _)| *score).max()?;
+    let best: Vec<_> = scored
+        .into_iter()
+        .filter(|(score, _)| *score == best_score)
+        .map(|(_, mv)| mv)
+        .collect();
+    best.choose(&mut thread_rng()).copied()
+}
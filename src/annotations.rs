@@ -0,0 +1,376 @@
+use std::collections::BTreeMap;
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, pipeline::PrimitiveTopology},
+};
+use bevy_mod_picking::PickingCamera;
+
+use crate::{
+    board::{NewGameEvent, Square},
+    history::{Browse, History},
+    menu::AppState,
+    notation::square_name,
+    save::LoadGameEvent,
+};
+
+/// Colors of arrows and marks, as in the `[%cal]` and `[%csl]` PGN commands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnnotationColor {
+    Green,
+    Red,
+    Blue,
+    Yellow,
+}
+
+impl AnnotationColor {
+    fn letter(&self) -> char {
+        match self {
+            AnnotationColor::Green => 'G',
+            AnnotationColor::Red => 'R',
+            AnnotationColor::Blue => 'B',
+            AnnotationColor::Yellow => 'Y',
+        }
+    }
+
+    /// Green, or another color while holding Shift (red), Ctrl (blue) or Alt (yellow)
+    fn from_modifiers(keyboard_input: &Input<KeyCode>) -> Self {
+        let held = |left, right| keyboard_input.pressed(left) || keyboard_input.pressed(right);
+        if held(KeyCode::LShift, KeyCode::RShift) {
+            AnnotationColor::Red
+        } else if held(KeyCode::LControl, KeyCode::RControl) {
+            AnnotationColor::Blue
+        } else if held(KeyCode::LAlt, KeyCode::RAlt) {
+            AnnotationColor::Yellow
+        } else {
+            AnnotationColor::Green
+        }
+    }
+}
+
+/// Arrows and marked squares drawn on one position
+#[derive(Clone, Debug, Default)]
+pub struct PositionAnnotations {
+    pub arrows: Vec<((u8, u8), (u8, u8), AnnotationColor)>,
+    pub marks: Vec<((u8, u8), AnnotationColor)>,
+}
+
+impl PositionAnnotations {
+    pub fn is_empty(&self) -> bool {
+        self.arrows.is_empty() && self.marks.is_empty()
+    }
+
+    /// PGN comment with the annotations, like `{[%csl Gd4][%cal Ge2e4]}`
+    pub fn pgn_comment(&self) -> String {
+        let mut comment = String::from("{");
+        if !self.marks.is_empty() {
+            let marks: Vec<String> = self
+                .marks
+                .iter()
+                .map(|&((x, y), color)| format!("{}{}", color.letter(), square_name(x, y)))
+                .collect();
+            comment.push_str(&format!("[%csl {}]", marks.join(",")));
+        }
+        if !self.arrows.is_empty() {
+            let arrows: Vec<String> = self
+                .arrows
+                .iter()
+                .map(|&(from, to, color)| {
+                    format!(
+                        "{}{}{}",
+                        color.letter(),
+                        square_name(from.0, from.1),
+                        square_name(to.0, to.1)
+                    )
+                })
+                .collect();
+            comment.push_str(&format!("[%cal {}]", arrows.join(",")));
+        }
+        comment.push('}');
+        comment
+    }
+
+    /// Drawing the same arrow or mark again erases it
+    fn toggle_arrow(&mut self, from: (u8, u8), to: (u8, u8), color: AnnotationColor) {
+        match self
+            .arrows
+            .iter()
+            .position(|&(f, t, c)| (f, t, c) == (from, to, color))
+        {
+            Some(index) => {
+                self.arrows.remove(index);
+            }
+            None => {
+                self.arrows.retain(|&(f, t, _)| (f, t) != (from, to));
+                self.arrows.push((from, to, color));
+            }
+        }
+    }
+
+    fn toggle_mark(&mut self, square: (u8, u8), color: AnnotationColor) {
+        match self
+            .marks
+            .iter()
+            .position(|&(s, c)| (s, c) == (square, color))
+        {
+            Some(index) => {
+                self.marks.remove(index);
+            }
+            None => {
+                self.marks.retain(|&(s, _)| s != square);
+                self.marks.push((square, color));
+            }
+        }
+    }
+}
+
+/// Annotations of each position of the game, by number of turns played
+#[derive(Default)]
+pub struct Annotations(pub BTreeMap<usize, PositionAnnotations>);
+
+/// Square the right button was pressed on, where an arrow starts
+#[derive(Default)]
+pub struct AnnotationStart(pub Option<(u8, u8)>);
+
+/// Mesh drawn over the board for an arrow or a mark
+struct AnnotationMesh;
+
+struct AnnotationMaterials {
+    green: Handle<StandardMaterial>,
+    red: Handle<StandardMaterial>,
+    blue: Handle<StandardMaterial>,
+    yellow: Handle<StandardMaterial>,
+    mark: Handle<Mesh>,
+}
+
+impl AnnotationMaterials {
+    fn color(&self, color: AnnotationColor) -> Handle<StandardMaterial> {
+        match color {
+            AnnotationColor::Green => self.green.clone(),
+            AnnotationColor::Red => self.red.clone(),
+            AnnotationColor::Blue => self.blue.clone(),
+            AnnotationColor::Yellow => self.yellow.clone(),
+        }
+    }
+}
+
+impl FromWorld for AnnotationMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .unwrap();
+        let mut meshes = world.get_resource_mut::<Assets<Mesh>>().unwrap();
+        // Unlit, so they're just as visible from every angle
+        let mut add = |color: Color| {
+            materials.add(StandardMaterial {
+                base_color: color,
+                unlit: true,
+                ..Default::default()
+            })
+        };
+        AnnotationMaterials {
+            green: add(Color::rgb(0.1, 0.6, 0.2)),
+            red: add(Color::rgb(0.8, 0.1, 0.1)),
+            blue: add(Color::rgb(0.1, 0.3, 0.8)),
+            yellow: add(Color::rgb(0.9, 0.7, 0.1)),
+            mark: meshes.add(mark_mesh()),
+        }
+    }
+}
+
+/// Height of the annotations over the board
+const ANNOTATION_HEIGHT: f32 = 0.02;
+const ARROW_WIDTH: f32 = 0.15;
+const ARROW_HEAD_WIDTH: f32 = 0.4;
+const ARROW_HEAD_LENGTH: f32 = 0.35;
+const MARK_WIDTH: f32 = 0.08;
+
+/// Flat mesh on the XZ plane from triangles, visible from above and below
+fn flat_mesh(triangles: &[[[f32; 2]; 3]]) -> Mesh {
+    let mut positions = Vec::new();
+    for triangle in triangles {
+        for &[x, z] in triangle.iter() {
+            positions.push([x, 0., z]);
+        }
+        for &[x, z] in triangle.iter().rev() {
+            positions.push([x, 0., z]);
+        }
+    }
+    let normals = vec![[0., 1., 0.]; positions.len()];
+    let uvs = vec![[0., 0.]; positions.len()];
+    let indices = Indices::U32((0..positions.len() as u32).collect());
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(indices));
+    mesh
+}
+
+/// Two triangles covering a rectangle
+fn rectangle(x0: f32, z0: f32, x1: f32, z1: f32) -> [[[f32; 2]; 3]; 2] {
+    [
+        [[x0, z0], [x1, z0], [x1, z1]],
+        [[x0, z0], [x1, z1], [x0, z1]],
+    ]
+}
+
+/// Arrow pointing along X, from the origin to `length`
+fn arrow_mesh(length: f32) -> Mesh {
+    let shaft_end = (length - ARROW_HEAD_LENGTH).max(0.);
+    let mut triangles = rectangle(0., -ARROW_WIDTH / 2., shaft_end, ARROW_WIDTH / 2.).to_vec();
+    triangles.push([
+        [shaft_end, -ARROW_HEAD_WIDTH / 2.],
+        [length, 0.],
+        [shaft_end, ARROW_HEAD_WIDTH / 2.],
+    ]);
+    flat_mesh(&triangles)
+}
+
+/// Frame along the edges of a square centered on the origin
+fn mark_mesh() -> Mesh {
+    let (outer, inner) = (0.45, 0.45 - MARK_WIDTH);
+    let mut triangles = Vec::new();
+    triangles.extend_from_slice(&rectangle(-outer, -outer, outer, -inner));
+    triangles.extend_from_slice(&rectangle(-outer, inner, outer, outer));
+    triangles.extend_from_slice(&rectangle(-outer, -inner, -inner, inner));
+    triangles.extend_from_slice(&rectangle(inner, -inner, outer, inner));
+    flat_mesh(&triangles)
+}
+
+/// Position the annotations are drawn on: the one browsed, or the current one
+fn annotated_ply(history: &History, browse: &Browse) -> usize {
+    browse.0.map_or(history.turns.len(), |index| index + 1)
+}
+
+/// Right-click marks a square, right-drag draws an arrow, left-click erases everything
+#[allow(clippy::too_many_arguments)]
+fn draw_annotations(
+    mouse_button_inputs: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    picking_camera_query: Query<&PickingCamera>,
+    squares_query: Query<&Square>,
+    history: Res<History>,
+    browse: Res<Browse>,
+    mut start: ResMut<AnnotationStart>,
+    mut annotations: ResMut<Annotations>,
+) {
+    let ply = annotated_ply(&history, &browse);
+    if mouse_button_inputs.just_pressed(MouseButton::Left)
+        && annotations.0.get(&ply).map_or(false, |a| !a.is_empty())
+    {
+        annotations.0.remove(&ply);
+    }
+
+    let pressed = mouse_button_inputs.just_pressed(MouseButton::Right);
+    let released = mouse_button_inputs.just_released(MouseButton::Right);
+    if !pressed && !released {
+        return;
+    }
+    let hovered = picking_camera_query
+        .iter()
+        .last()
+        .and_then(|picking_camera| picking_camera.intersect_top())
+        .and_then(|(entity, _)| squares_query.get(entity).ok())
+        .map(|square| (square.x, square.y));
+    if pressed {
+        start.0 = hovered;
+        return;
+    }
+    let from = if let Some(from) = start.0.take() {
+        from
+    } else {
+        return;
+    };
+    let to = if let Some(to) = hovered { to } else { return };
+    let color = AnnotationColor::from_modifiers(&keyboard_input);
+    let position = annotations.0.entry(ply).or_default();
+    if from == to {
+        position.toggle_mark(from, color);
+    } else {
+        position.toggle_arrow(from, to, color);
+    }
+}
+
+fn reset_annotations(
+    mut new_game_r: EventReader<NewGameEvent>,
+    mut load_game_r: EventReader<LoadGameEvent>,
+    mut annotations: ResMut<Annotations>,
+) {
+    let new_game = new_game_r.iter().next().is_some();
+    let load_game = load_game_r.iter().next().is_some();
+    if new_game || load_game {
+        annotations.0.clear();
+    }
+}
+
+/// Rebuilds the meshes of the arrows and marks of the position on the board
+fn update_annotation_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    materials: Res<AnnotationMaterials>,
+    annotations: Res<Annotations>,
+    history: Res<History>,
+    browse: Res<Browse>,
+    query: Query<Entity, With<AnnotationMesh>>,
+) {
+    if !annotations.is_changed() && !history.is_changed() && !browse.is_changed() {
+        return;
+    }
+    // The arrow meshes are dropped along with their entities
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let position = match annotations.0.get(&annotated_ply(&history, &browse)) {
+        Some(position) => position,
+        None => return,
+    };
+    for &((x, y), color) in position.marks.iter() {
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: materials.mark.clone(),
+                material: materials.color(color),
+                transform: Transform::from_translation(Vec3::new(
+                    x as f32,
+                    ANNOTATION_HEIGHT,
+                    y as f32,
+                )),
+                ..Default::default()
+            })
+            .insert(AnnotationMesh);
+    }
+    for &(from, to, color) in position.arrows.iter() {
+        let start = Vec3::new(from.0 as f32, ANNOTATION_HEIGHT, from.1 as f32);
+        let end = Vec3::new(to.0 as f32, ANNOTATION_HEIGHT, to.1 as f32);
+        let direction = end - start;
+        // Turn the arrow from X to its direction, around the vertical axis
+        let rotation = Quat::from_rotation_y(-direction.z.atan2(direction.x));
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(arrow_mesh(direction.length())),
+                material: materials.color(color),
+                transform: Transform {
+                    translation: start,
+                    rotation,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(AnnotationMesh);
+    }
+}
+
+pub struct AnnotationsPlugin;
+impl Plugin for AnnotationsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Annotations>()
+            .init_resource::<AnnotationStart>()
+            .init_resource::<AnnotationMaterials>()
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame).with_system(draw_annotations.system()),
+            )
+            .add_system(reset_annotations.system())
+            .add_system(update_annotation_meshes.system());
+    }
+}
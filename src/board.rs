@@ -1,8 +1,15 @@
 use crate::{
-    history::{History, Turn},
+    camera::BoardCamera,
+    fog::Fog,
+    headless::is_headless,
+    history::{Browse, History, Turn},
+    keyboard::{KeyboardCursor, MoveInput},
+    menu::{game_running, AppState, GameSetup},
+    opponent::AwaitingHost,
     pieces::*,
+    theme::{Theme, Themes},
 };
-use bevy::{app::AppExit, prelude::*};
+use bevy::prelude::*;
 use bevy_mod_picking::*;
 
 pub struct Square {
@@ -15,7 +22,8 @@ impl Square {
     }
 }
 
-struct MovableSquare;
+/// Square the selected piece can move to, highlighted on the board
+pub struct MovableSquare;
 
 fn create_board(
     mut commands: Commands,
@@ -46,12 +54,77 @@ fn create_board(
     }
 }
 
+/// File or rank name shown next to the board, at this position in the world
+struct BoardLabel(Vec3);
+
+/// Distance of the labels from the edge squares
+const LABEL_OFFSET: f32 = 0.8;
+
+fn create_labels(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 20.0,
+        color: Color::rgb(0.8, 0.8, 0.8),
+    };
+    // Files along White's edge, ranks along the a file
+    let files = (0..8).map(|y| {
+        (
+            ((b'a' + y) as char).to_string(),
+            Vec3::new(-LABEL_OFFSET, 0., y as f32),
+        )
+    });
+    let ranks = (0..8).map(|x| {
+        (
+            ((b'1' + x) as char).to_string(),
+            Vec3::new(x as f32, 0., -LABEL_OFFSET),
+        )
+    });
+    for (name, position) in files.chain(ranks) {
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                text: Text::with_section(name, text_style.clone(), Default::default()),
+                ..Default::default()
+            })
+            .insert(BoardLabel(position));
+    }
+}
+
+/// Keeps the labels next to their squares on screen, wherever the camera is
+fn place_labels(
+    windows: Res<Windows>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<BoardCamera>>,
+    mut labels_query: Query<(&BoardLabel, &Node, &mut Style)>,
+) {
+    let (camera, camera_transform) = if let Some(x) = camera_query.iter().next() {
+        x
+    } else {
+        return;
+    };
+    for (BoardLabel(position), node, mut style) in labels_query.iter_mut() {
+        if let Some(screen) = camera.world_to_screen(&windows, camera_transform, *position) {
+            style.position.left = Val::Px(screen.x - node.size.x / 2.);
+            style.position.bottom = Val::Px(screen.y - node.size.y / 2.);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn color_squares(
     materials: Res<SquareMaterials>,
     selected_piece: Res<Option<SelectedPiece>>,
     mut query: Query<(Entity, &Square, &mut Handle<StandardMaterial>), Without<MovableSquare>>,
     mut movable_query: Query<(Entity, &Square, &mut Handle<StandardMaterial>), With<MovableSquare>>,
     picking_camera_query: Query<&PickingCamera>,
+    cursor: Res<KeyboardCursor>,
+    history: Res<History>,
+    browse: Res<Browse>,
+    pieces_query: Query<&Piece, Without<Taken>>,
+    premove: Res<Premove>,
+    fog: Res<Fog>,
 ) {
     // Get entity under the cursor, if there is one
     let top_entity = match picking_camera_query.iter().last() {
@@ -62,12 +135,79 @@ fn color_squares(
     };
 
     let selected_square = selected_piece.as_ref().map(|x| x.square_entity);
+    // The keyboard cursor is highlighted just like the mouse
+    let highlighted = |entity: Entity, square: &Square| {
+        Some(entity) == top_entity || Some((square.x, square.y)) == cursor.0
+    };
+
+    // Last move played, or the one being looked at in the history
+    let last_turn = match browse.0 {
+        Some(index) => history.turns.get(index),
+        None => history.turns.last(),
+    };
+    // Where the opponent moved from would give away their pieces in the fog
+    let last_turn = last_turn.filter(|turn| fog.viewer.map_or(true, |color| color == turn.color));
+    let last_move = |square: &Square| {
+        last_turn.map_or(false, |turn| {
+            (square.x, square.y) == (turn.from_x, turn.from_y)
+                || (square.x, square.y) == (turn.to_x, turn.to_y)
+        })
+    };
+    // Kings that could be taken next move, and the pieces threatening them
+    let mut checked_kings = Vec::new();
+    let mut attackers = Vec::new();
+    if browse.0.is_none() && history.rules().has_check() {
+        let pieces: Vec<Piece> = pieces_query.iter().copied().collect();
+        for &color in [PieceColor::White, PieceColor::Black].iter() {
+            let king_attackers = king_attackers(&pieces, &history, color);
+            if king_attackers.is_empty() {
+                continue;
+            }
+            checked_kings.extend(
+                pieces
+                    .iter()
+                    .filter(|piece| piece.color == color && piece.piece_type == PieceType::King)
+                    .map(|piece| (piece.x, piece.y)),
+            );
+            attackers.extend(king_attackers);
+        }
+    }
+    let in_check = |square: &Square| checked_kings.contains(&(square.x, square.y));
+    let premoved = |square: &Square| {
+        premove.0.map_or(false, |event| {
+            (square.x, square.y) == event.from || (square.x, square.y) == event.to
+        })
+    };
+    let attacking = |square: &Square| attackers.contains(&(square.x, square.y));
+    let goals = history.rules().goal_squares();
+    let goal = |square: &Square| goals.contains(&(square.x, square.y));
+
     for (entity, square, mut material) in query.iter_mut() {
         // Change the material
-        *material = if Some(entity) == top_entity {
+        *material = if highlighted(entity, square) {
             materials.highlight_color.clone()
         } else if Some(entity) == selected_square {
             materials.selected_color.clone()
+        } else if fog.hides((square.x, square.y)) {
+            materials.fog_color.clone()
+        } else if premoved(square) {
+            materials.premove_color.clone()
+        } else if in_check(square) {
+            materials.check_color.clone()
+        } else if attacking(square) {
+            materials.attacker_color.clone()
+        } else if last_move(square) {
+            if square.is_white() {
+                materials.last_move_white_color.clone()
+            } else {
+                materials.last_move_black_color.clone()
+            }
+        } else if goal(square) {
+            if square.is_white() {
+                materials.goal_white_color.clone()
+            } else {
+                materials.goal_black_color.clone()
+            }
         } else if square.is_white() {
             materials.white_color.clone()
         } else {
@@ -75,10 +215,12 @@ fn color_squares(
         };
     }
     for (entity, square, mut material) in movable_query.iter_mut() {
-        *material = if Some(entity) == top_entity {
+        *material = if highlighted(entity, square) {
             materials.highlight_color.clone()
         } else if Some(entity) == selected_square {
             materials.selected_color.clone()
+        } else if in_check(square) {
+            materials.check_color.clone()
         } else if square.is_white() {
             materials.movable_white_color.clone()
         } else {
@@ -94,6 +236,14 @@ struct SquareMaterials {
     white_color: Handle<StandardMaterial>,
     movable_white_color: Handle<StandardMaterial>,
     movable_black_color: Handle<StandardMaterial>,
+    last_move_white_color: Handle<StandardMaterial>,
+    last_move_black_color: Handle<StandardMaterial>,
+    check_color: Handle<StandardMaterial>,
+    attacker_color: Handle<StandardMaterial>,
+    premove_color: Handle<StandardMaterial>,
+    goal_white_color: Handle<StandardMaterial>,
+    goal_black_color: Handle<StandardMaterial>,
+    fog_color: Handle<StandardMaterial>,
 }
 
 impl FromWorld for SquareMaterials {
@@ -102,17 +252,70 @@ impl FromWorld for SquareMaterials {
         let mut materials = world
             .get_resource_mut::<Assets<StandardMaterial>>()
             .unwrap();
-        SquareMaterials {
-            highlight_color: materials.add(Color::rgb(0.8, 0.3, 0.3).into()),
-            selected_color: materials.add(Color::rgb(0.9, 0.1, 0.1).into()),
-            black_color: materials.add(Color::rgb(0., 0.1, 0.1).into()),
-            white_color: materials.add(Color::rgb(1., 0.9, 0.9).into()),
-            movable_white_color: materials.add(Color::rgb(0.7, 0.9, 0.9).into()),
-            movable_black_color: materials.add(Color::rgb(0., 0.3, 0.3).into()),
+        let themes = world.get_resource::<Themes>().unwrap();
+        let mut add = || materials.add(StandardMaterial::default());
+        let square_materials = SquareMaterials {
+            highlight_color: add(),
+            selected_color: add(),
+            black_color: add(),
+            white_color: add(),
+            movable_white_color: add(),
+            movable_black_color: add(),
+            last_move_white_color: add(),
+            last_move_black_color: add(),
+            check_color: add(),
+            attacker_color: add(),
+            premove_color: add(),
+            goal_white_color: add(),
+            goal_black_color: add(),
+            fog_color: add(),
+        };
+        square_materials.apply_theme(themes.current(), &mut materials);
+        square_materials
+    }
+}
+
+impl SquareMaterials {
+    fn apply_theme(&self, theme: &Theme, materials: &mut Assets<StandardMaterial>) {
+        let colors = [
+            (&self.highlight_color, theme.highlight),
+            (&self.selected_color, theme.selected),
+            (&self.black_color, theme.black_square),
+            (&self.white_color, theme.white_square),
+            (&self.movable_white_color, theme.movable_white),
+            (&self.movable_black_color, theme.movable_black),
+            (&self.last_move_white_color, theme.last_move_white),
+            (&self.last_move_black_color, theme.last_move_black),
+            (&self.check_color, theme.check),
+            (&self.attacker_color, theme.attacker),
+            (&self.premove_color, theme.premove),
+            (&self.goal_white_color, theme.goal_white),
+            (&self.goal_black_color, theme.goal_black),
+            (&self.fog_color, theme.fog),
+        ];
+        for (handle, color) in colors.iter() {
+            if let Some(material) = materials.get_mut(*handle) {
+                material.base_color = *color;
+            }
+        }
+        // Glows, so the king in danger can't be missed
+        if let Some(material) = materials.get_mut(&self.check_color) {
+            let check = theme.check;
+            material.emissive = Color::rgb(check.r() * 0.6, check.g() * 0.6, check.b() * 0.6);
         }
     }
 }
 
+fn apply_square_theme(
+    themes: Res<Themes>,
+    square_materials: Res<SquareMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if themes.is_changed() {
+        square_materials.apply_theme(themes.current(), &mut materials);
+    }
+}
+
 pub struct PlayerTurn(pub PieceColor);
 impl Default for PlayerTurn {
     fn default() -> Self {
@@ -128,45 +331,108 @@ impl PlayerTurn {
     }
 }
 
-struct SelectedPiece {
-    square_entity: Entity,
-    piece_entity: Entity,
-    x: u8,
-    y: u8,
+/// Piece picked to be moved, and the square it was picked from
+pub struct SelectedPiece {
+    pub square_entity: Entity,
+    pub piece_entity: Entity,
+    pub x: u8,
+    pub y: u8,
 }
 
+/// Type of the piece in hand picked to be dropped, in drop variants
+#[derive(Default)]
+pub struct SelectedDrop(pub Option<PieceType>);
+
+/// Plays the piece on `from` to `to`, if that is a valid move for the player on turn
 #[derive(Debug, Clone, Copy)]
-struct MovePieceEvent(u8, u8);
+pub struct MovePieceEvent {
+    pub from: (u8, u8),
+    pub to: (u8, u8),
+    /// Piece a pawn reaching the last rank becomes, or `None` for the first the rules allow
+    pub promotion: Option<PieceType>,
+}
 
 #[allow(clippy::too_many_arguments)]
 fn select_square(
+    mut commands: Commands,
     mouse_button_inputs: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
     movable_squares_query: Query<&Square, With<MovableSquare>>,
-    squares_query: Query<&Square>,
-    pieces_query: Query<(Entity, &Piece)>,
+    squares_query: Query<(Entity, &Square)>,
+    pieces_query: Query<(Entity, &Piece), Without<Taken>>,
     picking_camera_query: Query<&PickingCamera>,
     mut selected_piece_res: ResMut<Option<SelectedPiece>>,
     turn: Res<PlayerTurn>,
+    setup: Res<GameSetup>,
+    browse: Res<Browse>,
+    cursor: Res<KeyboardCursor>,
+    move_input: Res<MoveInput>,
+    mut premove: ResMut<Premove>,
+    mut selected_drop: ResMut<SelectedDrop>,
     mut move_piece: EventWriter<MovePieceEvent>,
 ) {
-    // Only run if the left button is pressed
-    if !mouse_button_inputs.just_pressed(MouseButton::Left) {
+    let clicked = mouse_button_inputs.just_pressed(MouseButton::Left);
+    // Enter picks the keyboard cursor, unless a move is being typed
+    let entered = keyboard_input.just_pressed(KeyCode::Return) && move_input.text.is_empty();
+    // Only run if the left button or Enter is pressed
+    if !clicked && !entered {
+        return;
+    }
+    // Past positions can only be looked at
+    if browse.0.is_some() {
+        return;
+    }
+    // While the opponent is thinking, our next move can be queued
+    let premoving = !setup.is_local(turn.0);
+    let color = if premoving { turn.0.opposite() } else { turn.0 };
+    if !setup.is_local(color) {
         return;
     }
 
     let mut deselect = false;
 
     // Get the square under the cursor and set it as the selected
-    let picking_camera = picking_camera_query.single().expect("where is the camera?");
-    if let Some((square_entity, _intersection)) = picking_camera.intersect_top() {
-        let square = squares_query
+    let target = if clicked {
+        let picking_camera = picking_camera_query.single().expect("where is the camera?");
+        picking_camera
+            .intersect_top()
+            .map(|(square_entity, _intersection)| square_entity)
+    } else {
+        let cursor = if let Some(cursor) = cursor.0 {
+            cursor
+        } else {
+            return;
+        };
+        squares_query
+            .iter()
+            .find(|(_, square)| (square.x, square.y) == cursor)
+            .map(|(square_entity, _)| square_entity)
+    };
+    if let Some(square_entity) = target {
+        let (_, square) = squares_query
             .get(square_entity)
             .expect("where is the square");
-        // Don't select piece if no friendly piece is selected.
+        let movable = movable_squares_query
+            .iter()
+            .any(|move_square| square.x == move_square.x && square.y == move_square.y);
+        // A piece in hand goes on one of the highlighted squares
+        if let Some(piece_type) = selected_drop.0 {
+            selected_drop.0 = None;
+            if movable && !premoving {
+                move_piece.send(MovePieceEvent {
+                    from: pocket_square(color, piece_type),
+                    to: (square.x, square.y),
+                    promotion: None,
+                });
+                return;
+            }
+        }
+        // Don't select piece if no friendly piece is selected. A king castles on its own rook
         if let Some(piece_entity) = pieces_query
             .iter()
-            .find(|(_, piece)| piece.x == square.x && piece.y == square.y && piece.color == turn.0)
+            .find(|(_, piece)| piece.x == square.x && piece.y == square.y && piece.color == color)
             .map(|(entity, _)| entity)
+            .filter(|_| !(movable && selected_piece_res.is_some()))
         {
             let selected_piece = SelectedPiece {
                 square_entity,
@@ -175,16 +441,27 @@ fn select_square(
                 y: square.y,
             };
             selected_piece_res.insert(selected_piece);
+            // Pick it up too, so it can be dragged
+            if clicked {
+                commands.entity(piece_entity).insert(Lifted);
+            }
         } else {
             // Try to move piece otherwise
-            if movable_squares_query
-                .iter()
-                .any(|move_square| square.x == move_square.x && square.y == move_square.y)
-            {
-                let event = MovePieceEvent(square.x, square.y);
-                move_piece.send(event);
-            } else {
-                deselect = true;
+            match selected_piece_res.as_ref() {
+                Some(selected_piece) if movable => {
+                    let event = MovePieceEvent {
+                        from: (selected_piece.x, selected_piece.y),
+                        to: (square.x, square.y),
+                        promotion: None,
+                    };
+                    if premoving {
+                        premove.0 = Some(event);
+                        selected_piece_res.take();
+                    } else {
+                        move_piece.send(event);
+                    }
+                }
+                _ => deselect = true,
             }
         }
     } else {
@@ -193,43 +470,172 @@ fn select_square(
     if deselect {
         // Clicked outside of board or clicked outide of movable positions
         selected_piece_res.take();
+        if premove.0.is_some() {
+            premove.0 = None;
+        }
+    }
+}
+
+/// Promotion waiting for the player to pick the piece, when they aren't all queens
+#[derive(Default)]
+pub struct PendingPromotion {
+    /// Whether the player picks the piece their pawns become, set from the auto-queen setting
+    pub ask: bool,
+    pub event: Option<MovePieceEvent>,
+}
+
+/// Move queued during the opponent's turn, played right after theirs
+#[derive(Default)]
+pub struct Premove(pub Option<MovePieceEvent>);
+
+/// Plays the queued move as soon as it's our turn, if it's still valid
+fn play_premove(
+    turn: Res<PlayerTurn>,
+    setup: Res<GameSetup>,
+    history: Res<History>,
+    pieces_query: Query<&Piece, Without<Taken>>,
+    mut premove: ResMut<Premove>,
+    mut move_piece: EventWriter<MovePieceEvent>,
+) {
+    if premove.0.is_none() || !setup.is_local(turn.0) {
+        return;
+    }
+    let event = premove.0.take().unwrap();
+    let pieces: Vec<Piece> = pieces_query.iter().copied().collect();
+    let valid = pieces.iter().any(|piece| {
+        (piece.x, piece.y) == event.from
+            && piece.color == turn.0
+            && history
+                .rules()
+                .find_move(piece, &pieces, &history, event.to)
+                .is_some()
+    });
+    if valid {
+        move_piece.send(event);
+    } else {
+        println!(
+            "Premove from {:?} to {:?} isn't valid anymore",
+            event.from, event.to
+        );
+    }
+}
+
+/// Height at which dragged pieces float over the board
+const DRAG_HEIGHT: f32 = 0.5;
+
+/// Lifted pieces follow the cursor, and are played on the square they're dropped on
+#[allow(clippy::too_many_arguments)]
+fn drag_piece(
+    mut commands: Commands,
+    mouse_button_inputs: Res<Input<MouseButton>>,
+    turn: Res<PlayerTurn>,
+    mut premove: ResMut<Premove>,
+    mut selected_piece: ResMut<Option<SelectedPiece>>,
+    picking_camera_query: Query<&PickingCamera>,
+    squares_query: Query<&Square>,
+    movable_squares_query: Query<&Square, With<MovableSquare>>,
+    mut lifted_query: Query<(Entity, &Piece, &mut Transform), With<Lifted>>,
+    mut move_piece: EventWriter<MovePieceEvent>,
+) {
+    let (entity, piece, mut transform) = if let Some(x) = lifted_query.iter_mut().next() {
+        x
+    } else {
+        return;
+    };
+    let hovered = picking_camera_query
+        .iter()
+        .last()
+        .and_then(|picking_camera| picking_camera.intersect_top());
+    if let Some((_, intersection)) = &hovered {
+        let position = intersection.position();
+        transform.translation = Vec3::new(position.x, DRAG_HEIGHT, position.z);
+    }
+
+    if !mouse_button_inputs.just_released(MouseButton::Left) {
+        return;
+    }
+    commands.entity(entity).remove::<Lifted>();
+    // Dropping it anywhere else lets it slide back, still selected for a second click
+    if let Some(square) =
+        hovered.and_then(|(square_entity, _)| squares_query.get(square_entity).ok())
+    {
+        if movable_squares_query
+            .iter()
+            .any(|move_square| square.x == move_square.x && square.y == move_square.y)
+        {
+            let event = MovePieceEvent {
+                from: (piece.x, piece.y),
+                to: (square.x, square.y),
+                promotion: None,
+            };
+            // Dragging during the opponent's turn queues the move
+            if piece.color == turn.0 {
+                move_piece.send(event);
+            } else {
+                premove.0 = Some(event);
+                selected_piece.take();
+            }
+        }
     }
 }
 
 fn highlight_moves(
     mut commands: Commands,
     selected_piece: Res<Option<SelectedPiece>>,
+    selected_drop: Res<SelectedDrop>,
     squares_query: Query<(Entity, &Square), Without<MovableSquare>>,
     movable_squares_query: Query<Entity, With<MovableSquare>>,
-    pieces_query: Query<&Piece>,
-    pieces_to_take_query: Query<(Entity, &Piece)>,
+    pieces_query: Query<&Piece, Without<Taken>>,
     history: Res<History>,
+    turn: Res<PlayerTurn>,
 ) {
-    if !selected_piece.is_changed() {
+    if !selected_piece.is_changed() && !selected_drop.is_changed() {
         return;
     }
     for entity in movable_squares_query.iter() {
         commands.entity(entity).remove::<MovableSquare>();
     }
-    if let Some(selected_piece) = selected_piece.as_ref() {
-        let pieces: Vec<Piece> = pieces_query.iter().copied().collect();
+    let pieces: Vec<Piece> = pieces_query.iter().copied().collect();
+    if let Some(piece_type) = selected_drop.0 {
+        let piece = Piece::in_pocket(turn.0, piece_type);
+        let targets: Vec<(u8, u8)> = history
+            .rules()
+            .moves(&piece, &pieces, &history)
+            .iter()
+            .map(|&(x, y, _)| (x, y))
+            .collect();
+        for (entity, square) in squares_query.iter() {
+            if targets.contains(&(square.x, square.y)) {
+                commands.entity(entity).insert(MovableSquare);
+            }
+        }
+    } else if let Some(selected_piece) = selected_piece.as_ref() {
         let piece = pieces_query
             .get(selected_piece.piece_entity)
             .expect("where is the piece");
-        let positions = piece.valid_positions(&pieces, &history);
+        let positions = if piece.color == turn.0 {
+            history.rules().moves(piece, &pieces, &history)
+        } else {
+            // Our other pieces may have moved out of the way by the time a premove is played
+            let pieces: Vec<Piece> = pieces
+                .iter()
+                .filter(|other| {
+                    other.color != piece.color || (other.x, other.y) == (piece.x, piece.y)
+                })
+                .copied()
+                .collect();
+            piece.valid_positions(&pieces, &history)
+        };
+        let mut targets: Vec<(u8, u8)> = positions.iter().map(|&(x, y, _)| (x, y)).collect();
+        // Castling can also be played on the square the king ends up on
+        for &(x, y, _) in positions.iter() {
+            if is_castling(&pieces, (piece.x, piece.y), (x, y)) {
+                targets.push((x, castling_files(piece.y, y).0));
+            }
+        }
         for (entity, square) in squares_query.iter() {
-            for &(x, y, takeable) in positions.iter() {
-                if square.x == x && square.y == y {
-                    commands.entity(entity).insert(MovableSquare);
-                    if let Some(takeable) = takeable {
-                        for (entity, piece) in pieces_to_take_query.iter() {
-                            if piece.x == takeable.0 && piece.y == takeable.1 {
-                                let takeable = Takeable(x, y);
-                                commands.entity(entity).insert(takeable);
-                            }
-                        }
-                    }
-                }
+            if targets.contains(&(square.x, square.y)) {
+                commands.entity(entity).insert(MovableSquare);
             }
         }
     }
@@ -238,45 +644,139 @@ fn highlight_moves(
 #[allow(clippy::too_many_arguments)]
 fn move_piece(
     mut commands: Commands,
+    piece_assets: Option<Res<PieceAssets>>,
+    setup: Res<GameSetup>,
     mut turn: ResMut<PlayerTurn>,
-    mut pieces_query: Query<&mut Piece, Without<Takeable>>,
-    target_pieces_query: Query<(Entity, &Takeable)>,
+    history: Res<History>,
+    mut pieces_query: Query<(Entity, &mut Piece), Without<Taken>>,
     mut reset_selected_event: EventWriter<ResetSelectedEvent>,
     mut turn_event_w: EventWriter<Turn>,
+    mut game_over_w: EventWriter<GameOverEvent>,
+    mut blast_w: EventWriter<CaptureBlast>,
+    awaiting_host: Res<AwaitingHost>,
+    mut pending_promotion: ResMut<PendingPromotion>,
     mut move_piece_r: EventReader<MovePieceEvent>,
-    selected_state: Res<Option<SelectedPiece>>,
 ) {
-    let &MovePieceEvent(to_x, to_y) = if let Some(x) = move_piece_r.iter().next() {
+    let &MovePieceEvent {
+        from,
+        to,
+        promotion,
+    } = if let Some(x) = move_piece_r.iter().next() {
         x
     } else {
         return;
     };
-    let selected_state = selected_state
-        .as_ref()
-        .expect("move without selected piece");
-    let mut selected_piece = pieces_query
-        .get_mut(selected_state.piece_entity)
-        .expect("invalid selected state");
-    let target_piece = target_pieces_query
+    // Until the host of a network game says which side is ours, we could be playing theirs
+    if awaiting_host.0 {
+        println!("Waiting for the host to start the game");
+        return;
+    }
+    // Pieces in hand are played by drop_piece
+    if dropped_piece(from).is_some() {
+        return;
+    }
+    let entities: Vec<(Entity, Piece)> = pieces_query
+        .iter_mut()
+        .map(|(entity, piece)| (entity, *piece))
+        .collect();
+    let (piece_entity, piece) = if let Some(&x) = entities
         .iter()
-        .find(|(_, takeable)| takeable.0 == to_x && takeable.1 == to_y);
-    if let Some((target_piece_entity, _)) = target_piece {
-        selected_piece.energy = selected_piece.energy.saturating_add(KILL_ENERGY);
-        // Mark the piece as taken
-        commands.entity(target_piece_entity).insert(Taken);
+        .find(|(_, piece)| piece.x == from.0 && piece.y == from.1 && piece.color == turn.0)
+    {
+        x
+    } else {
+        println!("No piece to move on {:?}", from);
+        return;
+    };
+    let pieces: Vec<Piece> = entities.iter().map(|(_, piece)| *piece).collect();
+    let rules = history.rules();
+    let mv = if let Some(x) = rules.find_move(&piece, &pieces, &history, to) {
+        x
+    } else {
+        println!("Invalid move from {:?} to {:?}", from, to);
+        return;
+    };
+    let (to_x, to_y, takeable) = mv;
+    // The move is played again once the player has picked the piece
+    if promotion.is_none()
+        && pending_promotion.ask
+        && setup.is_local(turn.0)
+        && promotes(&piece, (to_x, to_y))
+    {
+        pending_promotion.event = Some(MovePieceEvent {
+            from,
+            to,
+            promotion: None,
+        });
+        return;
+    }
+    pending_promotion.event = None;
+
+    let (_, mut selected_piece) = pieces_query
+        .get_mut(piece_entity)
+        .expect("invalid selected state");
+    if let Some(Takeable(x, y)) = takeable {
+        if let Some(&(target_piece_entity, _)) = entities
+            .iter()
+            .find(|(_, piece)| piece.x == x && piece.y == y)
+        {
+            if setup.energy {
+                selected_piece.energy = selected_piece.energy.saturating_add(KILL_ENERGY);
+            }
+            // Mark the piece as taken
+            commands.entity(target_piece_entity).insert(Taken);
+        }
+        // Other pieces the rules remove with it, looked up on the position before the move
+        for square in rules.capture_effects(&pieces, &piece, (to_x, to_y)) {
+            blast_w.send(CaptureBlast(square.0, square.1));
+            let entity = if square == (to_x, to_y) {
+                Some(piece_entity)
+            } else {
+                entities
+                    .iter()
+                    .find(|(_, other)| (other.x, other.y) == square && square != from)
+                    .map(|&(entity, _)| entity)
+            };
+            if let Some(entity) = entity {
+                commands.entity(entity).insert(Taken);
+            }
+        }
     }
     // Move the selected piece to the selected square
-    let event_turn = Turn {
-        color: turn.0,
-        piece_type: selected_piece.piece_type,
-        from_x: selected_piece.x,
-        from_y: selected_piece.y,
-        to_x,
-        to_y,
-    };
+    let mut after = pieces.clone();
+    let removed = rules.play(&mut after, from, mv, promotion);
+    let event_turn = Turn::new(&piece, (to_x, to_y), &removed, &after, &history);
     // Move piece
     selected_piece.x = to_x;
     selected_piece.y = to_y;
+    selected_piece.can_castle = false;
+    // A promoted pawn is replaced, as the new piece has another model
+    if let Some(piece_type) = event_turn.promotion {
+        let promoted = Piece {
+            piece_type,
+            promoted: true,
+            ..*selected_piece
+        };
+        commands.entity(piece_entity).despawn_recursive();
+        spawn_piece(&mut commands, piece_assets.as_deref(), promoted);
+    }
+    if is_castling(&pieces, from, (to_x, to_y)) {
+        // The king and the rook go to their castling files
+        let (king_y, rook_y) = castling_files(from.1, to_y);
+        selected_piece.y = king_y;
+        if let Some(&(rook_entity, _)) = entities
+            .iter()
+            .find(|(_, piece)| (piece.x, piece.y) == (to_x, to_y))
+        {
+            let (_, mut rook) = pieces_query.get_mut(rook_entity).unwrap();
+            rook.y = rook_y;
+            rook.can_castle = false;
+        }
+    }
+
+    if let Some(result) = result_after(&history, &after, event_turn) {
+        game_over_w.send(GameOverEvent(result));
+    }
 
     // Change turn
     turn_event_w.send(event_turn);
@@ -284,87 +784,244 @@ fn move_piece(
     reset_selected_event.send(ResetSelectedEvent);
 }
 
-struct ResetSelectedEvent;
+/// Puts a piece in hand on the board, in drop variants
+#[allow(clippy::too_many_arguments)]
+fn drop_piece(
+    mut commands: Commands,
+    piece_assets: Option<Res<PieceAssets>>,
+    mut turn: ResMut<PlayerTurn>,
+    history: Res<History>,
+    pieces_query: Query<&Piece, Without<Taken>>,
+    mut reset_selected_event: EventWriter<ResetSelectedEvent>,
+    mut turn_event_w: EventWriter<Turn>,
+    mut game_over_w: EventWriter<GameOverEvent>,
+    awaiting_host: Res<AwaitingHost>,
+    mut move_piece_r: EventReader<MovePieceEvent>,
+) {
+    let &MovePieceEvent { from, to, .. } = if let Some(x) = move_piece_r.iter().next() {
+        x
+    } else {
+        return;
+    };
+    if awaiting_host.0 || dropped_piece(from).is_none() {
+        return;
+    }
+    let pieces: Vec<Piece> = pieces_query.iter().copied().collect();
+    let rules = history.rules();
+    let dropped = history
+        .piece_on(&pieces, from)
+        .filter(|piece| piece.color == turn.0)
+        .and_then(|piece| Some((piece, rules.find_move(&piece, &pieces, &history, to)?)));
+    let (piece, mv) = if let Some(x) = dropped {
+        x
+    } else {
+        println!("Invalid drop from {:?} to {:?}", from, to);
+        return;
+    };
+    let mut after = pieces.clone();
+    let removed = rules.play(&mut after, from, mv, None);
+    spawn_piece(
+        &mut commands,
+        piece_assets.as_deref(),
+        Piece {
+            x: to.0,
+            y: to.1,
+            ..piece
+        },
+    );
+    let event_turn = Turn::new(&piece, to, &removed, &after, &history);
+    if let Some(result) = result_after(&history, &after, event_turn) {
+        game_over_w.send(GameOverEvent(result));
+    }
+
+    turn_event_w.send(event_turn);
+    turn.change();
+    reset_selected_event.send(ResetSelectedEvent);
+}
+
+/// How the game ended, if `turn` ended it, leaving `after` on the board
+fn result_after(history: &History, after: &[Piece], turn: Turn) -> Option<GameResult> {
+    let mut after_history = history.clone();
+    after_history.turns.push(turn);
+    history
+        .rules()
+        .result(after, &after_history, turn.color.opposite())
+}
+
+pub struct ResetSelectedEvent;
+
+/// Square where a piece was blown up by a capture
+pub struct CaptureBlast(pub u8, pub u8);
 
 fn reset_selected(
     mut commands: Commands,
     mut event_reader: EventReader<ResetSelectedEvent>,
     movable_query: Query<Entity, With<MovableSquare>>,
-    takeable_query: Query<Entity, With<Takeable>>,
     mut selected_piece: ResMut<Option<SelectedPiece>>,
+    mut selected_drop: ResMut<SelectedDrop>,
 ) {
     for _event in event_reader.iter() {
         for entity in movable_query.iter() {
             commands.entity(entity).remove::<MovableSquare>();
         }
-        for entity in takeable_query.iter() {
-            commands.entity(entity).remove::<Takeable>();
-        }
         selected_piece.take();
+        selected_drop.0 = None;
     }
 }
 
-struct Taken;
-fn despawn_taken_pieces(
-    mut commands: Commands,
-    mut app_exit_events: EventWriter<AppExit>,
-    query: Query<(Entity, &Piece, &Taken)>,
+pub struct Taken;
+fn despawn_taken_pieces(mut commands: Commands, query: Query<Entity, With<Taken>>) {
+    for entity in query.iter() {
+        // Despawn piece and children
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EndReason {
+    KingTaken,
+    KingExploded,
+    Timeout,
+    Checkmate,
+    Stalemate,
+    HillReached,
+    ThreeChecks,
+    RaceWon,
+    /// Every piece has been lost, which wins in antichess
+    PiecesGone,
+    /// Every white pawn of a horde game has been taken
+    HordeTaken,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GameResult {
+    /// `None` for a draw
+    pub winner: Option<PieceColor>,
+    pub reason: EndReason,
+}
+
+pub struct GameOverEvent(pub GameResult);
+
+fn end_game(
+    mut game_over_r: EventReader<GameOverEvent>,
+    mut result: ResMut<Option<GameResult>>,
+    mut state: ResMut<State<AppState>>,
 ) {
-    for (entity, piece, _taken) in query.iter() {
-        // If the king is taken, we should exit
-        if piece.piece_type == PieceType::King {
-            println!(
-                "{} won! Thanks for playing!",
-                match piece.color {
-                    PieceColor::White => "Black",
-                    PieceColor::Black => "White",
-                }
-            );
-            app_exit_events.send(AppExit);
+    if let Some(GameOverEvent(game_result)) = game_over_r.iter().next() {
+        match state.current() {
+            AppState::InGame => state.set(AppState::GameOver).unwrap(),
+            // The settings opened over the game are closed too
+            AppState::Settings => state.replace(AppState::GameOver).unwrap(),
+            _ => return,
         }
+        *result = Some(*game_result);
+    }
+}
 
-        // Despawn piece and children
+/// Starts a new game from the position in the setup
+pub struct NewGameEvent;
+
+#[allow(clippy::too_many_arguments)]
+fn new_game(
+    mut commands: Commands,
+    mut new_game_r: EventReader<NewGameEvent>,
+    setup: Res<GameSetup>,
+    piece_assets: Option<Res<PieceAssets>>,
+    pieces_query: Query<Entity, With<Piece>>,
+    mut turn: ResMut<PlayerTurn>,
+    mut history: ResMut<History>,
+    mut result: ResMut<Option<GameResult>>,
+    mut premove: ResMut<Premove>,
+    mut pending_promotion: ResMut<PendingPromotion>,
+    mut reset_selected_event: EventWriter<ResetSelectedEvent>,
+) {
+    if new_game_r.iter().next().is_none() {
+        return;
+    }
+    for entity in pieces_query.iter() {
         commands.entity(entity).despawn_recursive();
     }
+    *history = History::new(setup.start_fen(), setup.variant);
+    let start = history.start();
+    for &piece in start.pieces.iter() {
+        spawn_piece(&mut commands, piece_assets.as_deref(), piece);
+    }
+    turn.0 = start.turn;
+    *result = None;
+    premove.0 = None;
+    pending_promotion.event = None;
+    reset_selected_event.send(ResetSelectedEvent);
 }
 
 pub struct BoardPlugin;
 impl Plugin for BoardPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.init_resource::<SquareMaterials>()
-            .init_resource::<PlayerTurn>()
+        app.init_resource::<PlayerTurn>()
             .init_resource::<Option<SelectedPiece>>()
+            .init_resource::<Option<GameResult>>()
+            .init_resource::<Premove>()
+            .init_resource::<PendingPromotion>()
+            .init_resource::<SelectedDrop>()
             .add_event::<ResetSelectedEvent>()
             .add_event::<MovePieceEvent>()
-            .add_startup_system(create_board.system())
-            .add_system(color_squares.system())
-            .add_system(select_square.system().label("select_square"))
-            .add_system(
-                // move_piece needs to run before select_piece
-                move_piece
-                    .system()
-                    .after("select_square")
-                    .before("select_piece")
-                    .label("move_piece"),
+            .add_event::<NewGameEvent>()
+            .add_event::<GameOverEvent>()
+            .add_event::<CaptureBlast>()
+            .add_system(new_game.system().label("new_game").before("select_square"))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(play_premove.system().before("move_piece")),
+            )
+            // The opponent keeps playing while the settings are open over the game
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game_running.system())
+                    .with_system(
+                        move_piece
+                            .system()
+                            .after("select_square")
+                            .label("move_piece"),
+                    )
+                    .with_system(
+                        drop_piece
+                            .system()
+                            .after("select_square")
+                            .label("move_piece"),
+                    ),
             )
-            // .add_system(
-            //     select_piece
-            //         .system()
-            //         .after("select_square")
-            //         .label("select_piece"),
-            // )
-            .add_system(highlight_moves.system().after("select_piece"))
+            // Once the move is played and the selection reset, so nothing stays highlighted
             .add_system(
-                despawn_taken_pieces
+                highlight_moves
                     .system()
-                    .after("move_piece")
-                    .before("select_piece"),
+                    .after("select_square")
+                    .after("reset_selected"),
             )
+            .add_system(despawn_taken_pieces.system().after("move_piece"))
             .add_system(
                 reset_selected
                     .system()
                     .after("move_piece")
-                    .before("select_piece"),
+                    .label("reset_selected"),
+            )
+            .add_system(end_game.system().after("move_piece"));
+        if is_headless(app) {
+            return;
+        }
+        app.init_resource::<SquareMaterials>()
+            .add_startup_system(create_board.system())
+            .add_startup_system(create_labels.system())
+            .add_system(place_labels.system())
+            .add_system(color_squares.system())
+            .add_system(apply_square_theme.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(select_square.system().label("select_square"))
+                    .with_system(
+                        drag_piece
+                            .system()
+                            .after("select_square")
+                            .before("move_piece"),
+                    ),
             );
     }
 }
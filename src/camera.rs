@@ -0,0 +1,262 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    input::mouse::{MouseMotion, MouseWheel},
+    prelude::*,
+};
+
+use crate::{
+    annotations::AnnotationStart,
+    board::PlayerTurn,
+    menu::{AppState, GameSetup, OpponentKind},
+    pieces::PieceColor,
+    ui::MOVE_LIST_WIDTH,
+};
+
+/// Camera looking at the board
+pub struct BoardCamera;
+
+/// Position of the camera around the center of the board
+#[derive(Clone, Copy, Debug)]
+pub struct Orbit {
+    /// Angle around the board, 0 being behind Black's pieces
+    pub yaw: f32,
+    /// Angle above the board, from 0 (level with it) to looking straight down
+    pub pitch: f32,
+    pub distance: f32,
+}
+
+impl Default for Orbit {
+    fn default() -> Self {
+        Orbit {
+            yaw: side_yaw(PieceColor::White),
+            pitch: 1.1,
+            distance: 13.,
+        }
+    }
+}
+
+impl Orbit {
+    pub fn transform(&self) -> Transform {
+        let offset = Vec3::new(
+            self.pitch.cos() * self.yaw.cos(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.sin(),
+        ) * self.distance;
+        Transform::from_translation(board_center() + offset).looking_at(board_center(), Vec3::Y)
+    }
+}
+
+/// Views the camera can jump to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraPreset {
+    /// Straight down, like a 2D board
+    TopDown,
+    WhiteSide,
+    BlackSide,
+    /// Low and slowly turning around the board
+    Cinematic,
+}
+
+/// Where the camera is going, and the side of the board it's on
+pub struct BoardView {
+    pub side: PieceColor,
+    pub orbit: Orbit,
+    /// Keep turning around the board, until the camera is moved by hand
+    pub spin: bool,
+}
+
+impl Default for BoardView {
+    fn default() -> Self {
+        BoardView {
+            side: PieceColor::White,
+            orbit: Orbit::default(),
+            spin: false,
+        }
+    }
+}
+
+impl BoardView {
+    /// Turns around the board to the side of `color`, keeping the height and zoom
+    pub fn look_from(&mut self, color: PieceColor) {
+        self.side = color;
+        self.orbit.yaw = side_yaw(color);
+        self.spin = false;
+    }
+
+    pub fn set_preset(&mut self, preset: CameraPreset) {
+        let side = match preset {
+            CameraPreset::WhiteSide => PieceColor::White,
+            CameraPreset::BlackSide => PieceColor::Black,
+            CameraPreset::TopDown | CameraPreset::Cinematic => self.side,
+        };
+        self.look_from(side);
+        let (pitch, distance) = match preset {
+            CameraPreset::TopDown => (MAX_PITCH, 11.),
+            CameraPreset::WhiteSide | CameraPreset::BlackSide => {
+                (Orbit::default().pitch, Orbit::default().distance)
+            }
+            CameraPreset::Cinematic => (0.4, 10.),
+        };
+        self.orbit.pitch = pitch;
+        self.orbit.distance = distance;
+        self.spin = preset == CameraPreset::Cinematic;
+    }
+}
+
+/// Middle of the board, which the camera turns around
+fn board_center() -> Vec3 {
+    Vec3::new(3.5, 0., 3.5)
+}
+
+/// How fast the camera moves to where it's going, higher is faster
+const CAMERA_SPEED: f32 = 6.;
+/// Radians turned per pixel of mouse movement
+const DRAG_SENSITIVITY: f32 = 0.005;
+/// Turning speed of the cinematic view, in radians per second
+const SPIN_SPEED: f32 = 0.15;
+const MIN_PITCH: f32 = 0.15;
+/// Just short of straight down, where the camera's up direction is undefined
+const MAX_PITCH: f32 = PI / 2. - 0.01;
+const MIN_DISTANCE: f32 = 5.;
+const MAX_DISTANCE: f32 = 25.;
+
+fn side_yaw(color: PieceColor) -> f32 {
+    match color {
+        PieceColor::White => PI,
+        PieceColor::Black => 0.,
+    }
+}
+
+/// Angle from `from` to `to`, going the short way around
+fn angle_between(from: f32, to: f32) -> f32 {
+    (to - from + PI).rem_euclid(2. * PI) - PI
+}
+
+/// Looks from the side of the player, or of the player on turn in hot-seat games
+fn face_player(setup: Res<GameSetup>, turn: Res<PlayerTurn>, mut view: ResMut<BoardView>) {
+    let side = if setup.opponent == OpponentKind::Human {
+        turn.0
+    } else {
+        setup.player_color
+    };
+    view.look_from(side);
+}
+
+/// Both players share the screen in hot-seat games, so the board turns to whoever is on turn
+fn follow_turn(setup: Res<GameSetup>, turn: Res<PlayerTurn>, mut view: ResMut<BoardView>) {
+    if turn.is_changed() && setup.opponent == OpponentKind::Human {
+        view.look_from(turn.0);
+    }
+}
+
+/// Tab flips the board, F1 to F4 switch between the preset views
+fn camera_keys(keyboard_input: Res<Input<KeyCode>>, mut view: ResMut<BoardView>) {
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        let side = view.side.opposite();
+        view.look_from(side);
+    }
+    let presets = [
+        (KeyCode::F1, CameraPreset::TopDown),
+        (KeyCode::F2, CameraPreset::WhiteSide),
+        (KeyCode::F3, CameraPreset::BlackSide),
+        (KeyCode::F4, CameraPreset::Cinematic),
+    ];
+    for &(key, preset) in presets.iter() {
+        if keyboard_input.just_pressed(key) {
+            view.set_preset(preset);
+        }
+    }
+}
+
+/// Dragging with the middle mouse button, or the right one off the board, turns around the
+/// board, the wheel zooms
+fn orbit_with_mouse(
+    mouse_button_inputs: Res<Input<MouseButton>>,
+    annotation_start: Res<AnnotationStart>,
+    windows: Res<Windows>,
+    mut mouse_motion_r: EventReader<MouseMotion>,
+    mut mouse_wheel_r: EventReader<MouseWheel>,
+    mut view: ResMut<BoardView>,
+) {
+    let motion = mouse_motion_r
+        .iter()
+        .fold(Vec2::ZERO, |motion, event| motion + event.delta);
+    let scroll: f32 = mouse_wheel_r.iter().map(|event| event.y).sum();
+    // Right-dragging from a square draws an arrow instead
+    let dragging = (mouse_button_inputs.pressed(MouseButton::Middle)
+        || (mouse_button_inputs.pressed(MouseButton::Right) && annotation_start.0.is_none()))
+        && motion != Vec2::ZERO;
+    // The wheel scrolls the move list when over it
+    let over_move_list = windows
+        .get_primary()
+        .and_then(|window| {
+            Some(window.cursor_position()?.x > window.width() - MOVE_LIST_WIDTH - 10.)
+        })
+        .unwrap_or(false);
+    let zooming = scroll != 0. && !over_move_list;
+    if !dragging && !zooming {
+        return;
+    }
+    let view = &mut *view;
+    view.spin = false;
+    if dragging {
+        view.orbit.yaw += motion.x * DRAG_SENSITIVITY;
+        view.orbit.pitch =
+            (view.orbit.pitch + motion.y * DRAG_SENSITIVITY).clamp(MIN_PITCH, MAX_PITCH);
+        // The side is the one the camera is closest to
+        view.side = if view.orbit.yaw.cos() < 0. {
+            PieceColor::White
+        } else {
+            PieceColor::Black
+        };
+    }
+    if zooming {
+        view.orbit.distance =
+            (view.orbit.distance * (1. - scroll * 0.1)).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+}
+
+fn spin_camera(time: Res<Time>, mut view: ResMut<BoardView>) {
+    if view.spin {
+        view.orbit.yaw += SPIN_SPEED * time.delta_seconds();
+    }
+}
+
+/// Moves the camera smoothly towards the orbit of the view
+fn animate_camera(
+    time: Res<Time>,
+    view: Res<BoardView>,
+    mut query: Query<(&mut Orbit, &mut Transform), With<BoardCamera>>,
+) {
+    let t = 1. - (-CAMERA_SPEED * time.delta_seconds()).exp();
+    let target = view.orbit;
+    for (mut orbit, mut transform) in query.iter_mut() {
+        let yaw = angle_between(orbit.yaw, target.yaw);
+        let pitch = target.pitch - orbit.pitch;
+        let distance = target.distance - orbit.distance;
+        if yaw.abs() < 1e-4 && pitch.abs() < 1e-4 && distance.abs() < 1e-4 {
+            continue;
+        }
+        orbit.yaw += yaw * t;
+        orbit.pitch += pitch * t;
+        orbit.distance += distance * t;
+        *transform = orbit.transform();
+    }
+}
+
+pub struct CameraPlugin;
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<BoardView>()
+            .add_system(orbit_with_mouse.system())
+            .add_system(spin_camera.system())
+            .add_system(animate_camera.system())
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(face_player.system()))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(follow_turn.system())
+                    .with_system(camera_keys.system()),
+            );
+    }
+}
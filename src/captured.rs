@@ -0,0 +1,156 @@
+use bevy::prelude::*;
+
+use crate::{
+    ai::piece_value,
+    board::NewGameEvent,
+    headless::is_headless,
+    history::{History, Turn},
+    pieces::*,
+    save::LoadGameEvent,
+};
+
+/// Pieces taken by each side so far
+#[derive(Default)]
+pub struct Captured {
+    pub by_white: Vec<PieceType>,
+    pub by_black: Vec<PieceType>,
+}
+
+impl Captured {
+    pub fn by(&self, color: PieceColor) -> &[PieceType] {
+        match color {
+            PieceColor::White => &self.by_white,
+            PieceColor::Black => &self.by_black,
+        }
+    }
+
+    fn by_mut(&mut self, color: PieceColor) -> &mut Vec<PieceType> {
+        match color {
+            PieceColor::White => &mut self.by_white,
+            PieceColor::Black => &mut self.by_black,
+        }
+    }
+
+    /// Material won by `color` minus the material it lost
+    pub fn balance(&self, color: PieceColor) -> i32 {
+        let won: i32 = self.by(color).iter().map(|&p| material_value(p)).sum();
+        let lost: i32 = self
+            .by(color.opposite())
+            .iter()
+            .map(|&p| material_value(p))
+            .sum();
+        won - lost
+    }
+
+    fn record(&mut self, turn: &Turn) {
+        if let Some(piece_type) = turn.captured {
+            self.by_mut(turn.color).push(piece_type);
+        }
+    }
+
+    /// Pieces taken in the turns of `history`. Pieces missing from the starting position, or gone
+    /// in an explosion, weren't taken by anyone
+    fn from_history(history: &History) -> Self {
+        let mut captured = Captured::default();
+        for turn in history.turns.iter() {
+            captured.record(turn);
+        }
+        captured
+    }
+}
+
+/// Kings aren't counted, taking one ends the game
+fn material_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::King => 0,
+        _ => piece_value(piece_type),
+    }
+}
+
+/// Small model of a captured piece, next to the board
+struct CapturedPiece;
+
+/// Distance between the captured pieces in the tray
+const TRAY_SPACING: f32 = 0.5;
+
+/// Captured pieces in a row of the tray, as long as the board
+const TRAY_ROW_LENGTH: usize = 16;
+
+fn record_captures(mut captured: ResMut<Captured>, mut turn_event_r: EventReader<Turn>) {
+    for turn in turn_event_r.iter() {
+        captured.record(turn);
+    }
+}
+
+fn reset_captures(
+    mut new_game_r: EventReader<NewGameEvent>,
+    mut load_game_r: EventReader<LoadGameEvent>,
+    mut captured: ResMut<Captured>,
+) {
+    if new_game_r.iter().next().is_some() {
+        *captured = Captured::default();
+    }
+    if let Some(LoadGameEvent(save)) = load_game_r.iter().last() {
+        *captured = Captured::from_history(&save.history);
+    }
+}
+
+/// Lines the pieces taken by each side up along its edge of the board, most valuable first
+fn update_tray(
+    mut commands: Commands,
+    assets: Res<PieceAssets>,
+    captured: Res<Captured>,
+    query: Query<Entity, With<CapturedPiece>>,
+) {
+    if !captured.is_changed() {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for &color in [PieceColor::White, PieceColor::Black].iter() {
+        let mut piece_types = captured.by(color).to_vec();
+        piece_types.sort_by_key(|&p| -piece_value(p));
+        for (i, piece_type) in piece_types.into_iter().enumerate() {
+            // Rows past the first, for the horde, go further from the board
+            let along = (i % TRAY_ROW_LENGTH) as f32 * TRAY_SPACING;
+            let away = (i / TRAY_ROW_LENGTH) as f32 * TRAY_SPACING;
+            // White's captures are on the side of the a file, Black's on the side of the h file
+            let translation = match color {
+                PieceColor::White => Vec3::new(along, 0., -1.5 - away),
+                PieceColor::Black => Vec3::new(7. - along, 0., 8.5 + away),
+            };
+            let piece = Piece {
+                color: color.opposite(),
+                piece_type,
+                x: 0,
+                y: 0,
+                energy: 0,
+                can_castle: false,
+                promoted: false,
+            };
+            let entity = spawn_piece(&mut commands, Some(&assets), piece);
+            commands
+                .entity(entity)
+                .remove::<Piece>()
+                .insert(CapturedPiece)
+                .insert(Transform {
+                    translation,
+                    scale: Vec3::splat(0.5),
+                    ..Default::default()
+                });
+        }
+    }
+}
+
+pub struct CapturedPlugin;
+impl Plugin for CapturedPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Captured>()
+            .add_system(record_captures.system())
+            .add_system(reset_captures.system());
+        if !is_headless(app) {
+            app.add_system(update_tray.system());
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use structopt::StructOpt;
+
+use crate::{
+    board::NewGameEvent,
+    clock::TimeControl,
+    config::Config,
+    fen::parse_fen,
+    headless::Headless,
+    menu::{AppState, GameSetup, OpponentKind},
+    pgn::import_pgn,
+    pieces::{PieceColor, CHESS960_POSITIONS},
+    save::{read_save, LoadGameEvent, SaveGame},
+    variant::GameVariant,
+};
+
+/// Settings overriding the config file, and a game to start right away instead of showing the
+/// main menu
+#[derive(Debug, StructOpt)]
+#[structopt(name = "bevy_chess")]
+pub struct Args {
+    /// Config file to use instead of the one in the user's config directory
+    #[structopt(long, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+    #[structopt(long)]
+    pub theme: Option<String>,
+    /// Squares per second
+    #[structopt(long)]
+    pub animation_speed: Option<f32>,
+    #[structopt(long)]
+    pub width: Option<f32>,
+    #[structopt(long)]
+    pub height: Option<f32>,
+    #[structopt(long)]
+    pub vsync: Option<bool>,
+    #[structopt(long)]
+    pub msaa: Option<u32>,
+    /// Time control, like `5+3`
+    #[structopt(long)]
+    pub time_control: Option<TimeControl>,
+    /// Moves the built-in AI looks ahead
+    #[structopt(long)]
+    pub ai_depth: Option<u8>,
+
+    /// Starts from this position, in FEN
+    #[structopt(long, conflicts_with = "load")]
+    pub fen: Option<String>,
+    /// Continues the game in a save file, or plays on from the moves of a PGN file
+    #[structopt(long, parse(from_os_str))]
+    pub load: Option<PathBuf>,
+    /// Rules to play by, by the name of the variant like classic-fun, standard or three-check
+    #[structopt(long, parse(try_from_str = parse_variant))]
+    pub variant: Option<GameVariant>,
+    /// Number of the Chess960 starting position, from 0 to 959, instead of a random one
+    #[structopt(
+        long,
+        conflicts_with_all = &["fen", "load"],
+        parse(try_from_str = parse_chess960_position)
+    )]
+    pub chess960_position: Option<u16>,
+    /// Plays against the built-in AI
+    #[structopt(long, conflicts_with_all = &["engine", "host", "connect"])]
+    pub ai: bool,
+    /// Plays against the UCI engine at this path
+    #[structopt(long, conflicts_with_all = &["host", "connect"])]
+    pub engine: Option<String>,
+    /// Waits for a network opponent on this address
+    #[structopt(long, conflicts_with = "connect")]
+    pub host: Option<String>,
+    /// Joins the network game hosted on this address
+    #[structopt(long)]
+    pub connect: Option<String>,
+    /// Color played against the AI, an engine or a network opponent
+    #[structopt(long, parse(try_from_str = parse_color))]
+    pub color: Option<PieceColor>,
+
+    /// Plays the game without a window, printing it as PGN once it's over
+    #[structopt(long)]
+    pub headless: bool,
+    /// Moves played first without a window, in SAN or coordinates, separated by spaces. The
+    /// built-in AI plays on after them
+    #[structopt(long, requires = "headless")]
+    pub moves: Option<String>,
+    /// Stops the game without a window after this many plies, 500 by default
+    #[structopt(long, requires = "headless")]
+    pub max_plies: Option<usize>,
+}
+
+fn parse_color(s: &str) -> Result<PieceColor, String> {
+    match s.to_lowercase().as_str() {
+        "white" | "w" => Ok(PieceColor::White),
+        "black" | "b" => Ok(PieceColor::Black),
+        _ => Err(format!("unknown color {}, expected white or black", s)),
+    }
+}
+
+/// Variant by name, ignoring case, spaces and dashes
+fn parse_variant(s: &str) -> Result<GameVariant, String> {
+    let simplify = |name: &str| {
+        name.chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase()
+    };
+    GameVariant::ALL
+        .iter()
+        .copied()
+        .find(|variant| simplify(variant.name()) == simplify(s))
+        .ok_or_else(|| format!("unknown variant {}", s))
+}
+
+fn parse_chess960_position(s: &str) -> Result<u16, String> {
+    s.parse()
+        .ok()
+        .filter(|&index| index < CHESS960_POSITIONS)
+        .ok_or_else(|| {
+            format!(
+                "invalid Chess960 position {}, expected 0 to {}",
+                s,
+                CHESS960_POSITIONS - 1
+            )
+        })
+}
+
+/// Game started from the command line
+pub enum Launch {
+    New,
+    Load(SaveGame),
+}
+
+impl Args {
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(theme) = &self.theme {
+            config.theme = theme.clone();
+        }
+        if let Some(animation_speed) = self.animation_speed {
+            config.animation_speed = animation_speed;
+        }
+        if let Some(width) = self.width {
+            config.window.width = width;
+        }
+        if let Some(height) = self.height {
+            config.window.height = height;
+        }
+        if let Some(vsync) = self.vsync {
+            config.window.vsync = vsync;
+        }
+        if let Some(msaa) = self.msaa {
+            config.window.msaa_samples = msaa;
+        }
+        if let Some(time_control) = self.time_control {
+            config.set_time_control(Some(time_control));
+        }
+        if let Some(ai_depth) = self.ai_depth {
+            config.game.ai_depth = ai_depth;
+        }
+        if let Some(engine) = &self.engine {
+            config.game.engine_path = engine.clone();
+        }
+    }
+
+    fn apply_setup(&self, setup: &mut GameSetup) {
+        if self.ai {
+            setup.opponent = OpponentKind::Ai;
+        }
+        if let Some(engine) = &self.engine {
+            setup.opponent = OpponentKind::Engine;
+            setup.engine_path = engine.clone();
+        }
+        if let Some(address) = &self.host {
+            setup.opponent = OpponentKind::Host;
+            setup.address = address.clone();
+        }
+        if let Some(address) = &self.connect {
+            setup.opponent = OpponentKind::Connect;
+            setup.address = address.clone();
+        }
+        if let Some(color) = self.color {
+            setup.player_color = color;
+        }
+        if let Some(variant) = self.variant {
+            setup.variant = variant;
+        }
+        if let Some(index) = self.chess960_position {
+            setup.variant = GameVariant::Chess960;
+            setup.chess960_position = Some(index);
+        }
+        if let Some(ai_depth) = self.ai_depth {
+            setup.ai_depth = ai_depth;
+        }
+    }
+
+    fn starts_game(&self) -> bool {
+        self.headless
+            || self.fen.is_some()
+            || self.load.is_some()
+            || self.variant.is_some()
+            || self.chess960_position.is_some()
+            || self.ai
+            || self.engine.is_some()
+            || self.host.is_some()
+            || self.connect.is_some()
+            || self.color.is_some()
+    }
+
+    pub fn headless(&self) -> Headless {
+        Headless {
+            moves: self
+                .moves
+                .iter()
+                .flat_map(|moves| moves.split_whitespace())
+                .map(str::to_string)
+                .collect(),
+            max_plies: self.max_plies.unwrap_or(Headless::default().max_plies),
+        }
+    }
+
+    /// Game to start, if any. `setup` starts with the defaults from the config
+    pub fn launch(&self, setup: &mut GameSetup) -> Result<Option<Launch>, String> {
+        if let Some(path) = &self.load {
+            let is_pgn = path
+                .extension()
+                .map_or(false, |ext| ext.eq_ignore_ascii_case("pgn"));
+            let mut save = if is_pgn {
+                let pgn = fs::read_to_string(path)
+                    .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+                SaveGame::from_history(import_pgn(&pgn)?, setup.clone())
+            } else {
+                read_save(path).map_err(|err| err.to_string())?
+            };
+            // The rules are the ones the game was played by
+            if let Some(variant) = self
+                .variant
+                .filter(|&variant| variant != save.history.variant)
+            {
+                return Err(format!(
+                    "{} is a {} game, it can't be played as {}",
+                    path.display(),
+                    save.history.variant.name(),
+                    variant.name()
+                ));
+            }
+            self.apply_setup(&mut save.setup);
+            return Ok(Some(Launch::Load(save)));
+        }
+        if !self.starts_game() {
+            return Ok(None);
+        }
+        if let Some(fen) = &self.fen {
+            parse_fen(fen)?;
+            setup.fen = Some(fen.clone());
+        }
+        self.apply_setup(setup);
+        Ok(Some(Launch::New))
+    }
+}
+
+/// Game waiting for the app to start
+#[derive(Default)]
+pub struct PendingLaunch(pub Option<Launch>);
+
+fn launch_game(
+    mut launch: ResMut<PendingLaunch>,
+    mut state: ResMut<State<AppState>>,
+    mut new_game_w: EventWriter<NewGameEvent>,
+    mut load_game_w: EventWriter<LoadGameEvent>,
+) {
+    match launch.0.take() {
+        Some(Launch::New) => new_game_w.send(NewGameEvent),
+        Some(Launch::Load(save)) => load_game_w.send(LoadGameEvent(save)),
+        None => return,
+    }
+    state.set(AppState::InGame).unwrap();
+}
+
+pub struct CliPlugin;
+impl Plugin for CliPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<PendingLaunch>()
+            .add_startup_system(launch_game.system().label("launch_game"));
+    }
+}
@@ -0,0 +1,162 @@
+use std::{fmt::Display, str::FromStr, time::Duration};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{EndReason, GameOverEvent, GameResult, NewGameEvent, PlayerTurn},
+    history::Turn,
+    menu::{game_running, GameSetup},
+    pieces::PieceColor,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub initial: Duration,
+    pub increment: Duration,
+}
+
+impl TimeControl {
+    pub const fn new(minutes: u64, increment_seconds: u64) -> Self {
+        TimeControl {
+            initial: Duration::from_secs(minutes * 60),
+            increment: Duration::from_secs(increment_seconds),
+        }
+    }
+}
+
+/// Time controls offered in the game setup
+pub const TIME_CONTROLS: [TimeControl; 6] = [
+    TimeControl::new(1, 0),
+    TimeControl::new(3, 2),
+    TimeControl::new(5, 0),
+    TimeControl::new(10, 0),
+    TimeControl::new(15, 10),
+    TimeControl::new(30, 0),
+];
+
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}+{}",
+            self.initial.as_secs() / 60,
+            self.increment.as_secs()
+        )
+    }
+}
+
+/// Parses `minutes+increment`, like `5+3`
+impl FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (minutes, increment) = s.split_once('+').unwrap_or((s, "0"));
+        match (minutes.trim().parse(), increment.trim().parse()) {
+            (Ok(minutes), Ok(increment)) => Ok(TimeControl::new(minutes, increment)),
+            _ => Err(format!(
+                "invalid time control {}, expected minutes+increment",
+                s
+            )),
+        }
+    }
+}
+
+/// Remaining time of each player. Clocks without a time control never run
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct Clocks {
+    pub time_control: Option<TimeControl>,
+    pub white: Duration,
+    pub black: Duration,
+    /// Time played since the start of the game
+    #[serde(default)]
+    pub elapsed: Duration,
+}
+
+impl Clocks {
+    pub fn new(time_control: Option<TimeControl>) -> Self {
+        let initial = time_control.map(|tc| tc.initial).unwrap_or_default();
+        Clocks {
+            time_control,
+            white: initial,
+            black: initial,
+            elapsed: Duration::default(),
+        }
+    }
+
+    pub fn remaining(&self, color: PieceColor) -> Duration {
+        match color {
+            PieceColor::White => self.white,
+            PieceColor::Black => self.black,
+        }
+    }
+
+    fn remaining_mut(&mut self, color: PieceColor) -> &mut Duration {
+        match color {
+            PieceColor::White => &mut self.white,
+            PieceColor::Black => &mut self.black,
+        }
+    }
+}
+
+/// Formats a clock as `m:ss`
+pub fn format_clock(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn reset_clocks(
+    mut new_game_r: EventReader<NewGameEvent>,
+    setup: Res<GameSetup>,
+    mut clocks: ResMut<Clocks>,
+) {
+    if new_game_r.iter().next().is_some() {
+        *clocks = Clocks::new(setup.time_control);
+    }
+}
+
+fn tick_clocks(
+    time: Res<Time>,
+    turn: Res<PlayerTurn>,
+    mut clocks: ResMut<Clocks>,
+    mut game_over_w: EventWriter<GameOverEvent>,
+) {
+    clocks.elapsed += time.delta();
+    if clocks.time_control.is_none() {
+        return;
+    }
+    let remaining = clocks.remaining_mut(turn.0);
+    if remaining.as_nanos() == 0 {
+        return;
+    }
+    *remaining = remaining.checked_sub(time.delta()).unwrap_or_default();
+    if remaining.as_nanos() == 0 {
+        game_over_w.send(GameOverEvent(GameResult {
+            winner: Some(turn.0.opposite()),
+            reason: EndReason::Timeout,
+        }));
+    }
+}
+
+fn add_increment(mut turn_event_r: EventReader<Turn>, mut clocks: ResMut<Clocks>) {
+    for turn in turn_event_r.iter() {
+        if let Some(time_control) = clocks.time_control {
+            *clocks.remaining_mut(turn.color) += time_control.increment;
+        }
+    }
+}
+
+pub struct ClockPlugin;
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Clocks>()
+            .add_system(reset_clocks.system())
+            .add_system(add_increment.system())
+            // Opening the settings during a game doesn't stop the clock
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game_running.system())
+                    .with_system(tick_clocks.system()),
+            );
+    }
+}
@@ -5,12 +5,14 @@ use bevy::{
     math::{Vec2, Vec3},
     prelude::{
         AppBuilder, AssetServer, Assets, BuildChildren, Color, Commands, Entity, EventReader,
-        Plugin, Query, Res, SpriteBundle, Transform, With,
+        EventWriter, Plugin, Query, Res, SpriteBundle, Transform, With,
     },
     sprite::{ColorMaterial, Sprite},
 };
 use rand::{thread_rng, Rng};
 
+use crate::board::{CaptureBlast, Square};
+
 struct Particle;
 struct Lifetime(i32);
 struct Velocity(Vec3);
@@ -18,15 +20,45 @@ struct Acceleration(Vec3);
 struct Alive(bool);
 
 struct Combust(Timer);
+/// Time left before a combust stops spawning particles
+struct Burning(Timer);
 
 pub struct StartCombust(pub Entity);
 
+const BURN_SECONDS: f32 = 0.6;
+
 fn create_combust(mut event_reader: EventReader<StartCombust>, mut commands: Commands) {
     for StartCombust(entity) in event_reader.iter() {
         commands
             .entity(*entity)
-            .insert(Combust(Timer::from_seconds(0.001, true)));
-        println!("COMBUST GOES BRRRRRRRR")
+            .insert(Combust(Timer::from_seconds(0.001, true)))
+            .insert(Burning(Timer::from_seconds(BURN_SECONDS, false)));
+    }
+}
+
+fn blast_squares(
+    mut blasts: EventReader<CaptureBlast>,
+    mut start_combust: EventWriter<StartCombust>,
+    squares: Query<(Entity, &Square)>,
+) {
+    for &CaptureBlast(x, y) in blasts.iter() {
+        if let Some((entity, _)) = squares
+            .iter()
+            .find(|(_, square)| (square.x, square.y) == (x, y))
+        {
+            start_combust.send(StartCombust(entity));
+        }
+    }
+}
+
+fn stop_combust(time: Res<Time>, mut commands: Commands, mut query: Query<(Entity, &mut Burning)>) {
+    for (entity, mut burning) in query.iter_mut() {
+        if burning.0.tick(time.delta()).just_finished() {
+            commands
+                .entity(entity)
+                .remove::<Combust>()
+                .remove::<Burning>();
+        }
     }
 }
 
@@ -135,7 +167,9 @@ impl Plugin for CombustPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<CombustMaterials>()
             .add_event::<StartCombust>()
+            .add_system(blast_squares.system())
             .add_system(create_combust.system())
+            .add_system(stop_combust.system())
             .add_system(spawn_particles.system())
             .add_system(kill_particles.system())
             .add_system(update_pos.system())
@@ -0,0 +1,266 @@
+use std::{fmt::Display, fs, io, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::TimeControl,
+    menu::{AppState, GameSetup},
+};
+
+/// Preferences read from `config.toml` at startup
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Name of the theme, see `assets/themes`
+    pub theme: String,
+    /// Speed of the pieces moving to their square, in squares per second
+    pub animation_speed: f32,
+    pub window: WindowConfig,
+    pub game: GameConfig,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: f32,
+    pub height: f32,
+    pub vsync: bool,
+    /// Antialiasing samples, 1 to turn it off
+    pub msaa_samples: u32,
+}
+
+/// Starting values of the new game menu
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    /// Like `5+3`, unlimited when missing
+    pub time_control: Option<String>,
+    pub ai_depth: u8,
+    pub engine_path: String,
+    /// Whether pawns reaching the last rank become queens, instead of asking for the piece
+    pub auto_queen: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            theme: "Classic".to_string(),
+            animation_speed: 8.,
+            window: WindowConfig::default(),
+            game: GameConfig::default(),
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            width: 1000.,
+            height: 1000.,
+            vsync: true,
+            msaa_samples: 8,
+        }
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        let setup = GameSetup::default();
+        GameConfig {
+            time_control: None,
+            ai_depth: setup.ai_depth,
+            engine_path: setup.engine_path,
+            auto_queen: true,
+        }
+    }
+}
+
+impl Config {
+    pub fn time_control(&self) -> Option<TimeControl> {
+        let time_control = self.game.time_control.as_ref()?;
+        match time_control.parse() {
+            Ok(time_control) => Some(time_control),
+            Err(err) => {
+                println!("Ignoring the default time control, {}", err);
+                None
+            }
+        }
+    }
+
+    pub fn set_time_control(&mut self, time_control: Option<TimeControl>) {
+        self.game.time_control = time_control.map(|time_control| time_control.to_string());
+    }
+
+    pub fn window_descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            title: "Chess!".to_string(),
+            width: self.window.width,
+            height: self.window.height,
+            vsync: self.window.vsync,
+            ..Default::default()
+        }
+    }
+
+    pub fn msaa(&self) -> Msaa {
+        Msaa {
+            samples: self.window.msaa_samples,
+        }
+    }
+
+    pub fn game_setup(&self) -> GameSetup {
+        GameSetup {
+            time_control: self.time_control(),
+            ai_depth: self.game.ai_depth,
+            engine_path: self.game.engine_path.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Copies the settings changed in the menu, which had `opened` when it was opened
+    #[allow(clippy::float_cmp)]
+    fn copy_changed_settings(&mut self, from: &Config, opened: &Config) {
+        if from.theme != opened.theme {
+            self.theme = from.theme.clone();
+        }
+        if from.animation_speed != opened.animation_speed {
+            self.animation_speed = from.animation_speed;
+        }
+        if from.window.msaa_samples != opened.window.msaa_samples {
+            self.window.msaa_samples = from.window.msaa_samples;
+        }
+        if from.game.time_control != opened.game.time_control {
+            self.game.time_control = from.game.time_control.clone();
+        }
+        if from.game.auto_queen != opened.game.auto_queen {
+            self.game.auto_queen = from.game.auto_queen;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Read(toml::de::Error),
+    Write(toml::ser::Error),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "couldn't access config file: {}", err),
+            ConfigError::Read(err) => write!(f, "config file is malformed: {}", err),
+            ConfigError::Write(err) => write!(f, "couldn't write config: {}", err),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        ConfigError::Read(err)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(err: toml::ser::Error) -> Self {
+        ConfigError::Write(err)
+    }
+}
+
+/// File the config was read from, and where the settings are saved
+pub struct ConfigPath(pub PathBuf);
+
+impl Default for ConfigPath {
+    fn default() -> Self {
+        ConfigPath(default_config_path())
+    }
+}
+
+/// `config.toml` in the user's config directory, falling back to the working directory
+pub fn default_config_path() -> PathBuf {
+    dirs::config_dir()
+        .map(|dir| dir.join("bevy_chess").join("config.toml"))
+        .unwrap_or_else(|| PathBuf::from("config.toml"))
+}
+
+pub fn read_config(path: &PathBuf) -> Result<Config, ConfigError> {
+    let contents = fs::read_to_string(path)?;
+    Ok(toml::from_str(&contents)?)
+}
+
+pub fn write_config(path: &PathBuf, config: &Config) -> Result<(), ConfigError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, toml::to_string_pretty(config)?)?;
+    Ok(())
+}
+
+/// Reads the config, creating the file with the defaults on the first run so it can be edited
+pub fn load_config(path: &PathBuf) -> Config {
+    if !path.exists() {
+        let config = Config::default();
+        if let Err(err) = write_config(path, &config) {
+            println!("Couldn't create config, {}", err);
+        }
+        return config;
+    }
+    match read_config(path) {
+        Ok(config) => config,
+        Err(err) => {
+            println!("Using the default config, {}", err);
+            Config::default()
+        }
+    }
+}
+
+/// Config as it was when the settings menu was opened
+#[derive(Default)]
+struct OpenedSettings(Option<Config>);
+
+fn open_settings(config: Res<Config>, mut opened: ResMut<OpenedSettings>) {
+    opened.0 = Some(config.clone());
+}
+
+/// Writes the settings changed in the menu, leaving the rest of the file alone. The command line
+/// overrides are only saved when they were changed there too
+fn save_settings(config: Res<Config>, path: Res<ConfigPath>, mut opened: ResMut<OpenedSettings>) {
+    let opened = match opened.0.take() {
+        Some(opened) => opened,
+        None => return,
+    };
+    let mut saved = match read_config(&path.0) {
+        Ok(saved) => saved,
+        Err(ConfigError::Io(_)) => Config::default(),
+        // Don't overwrite a file with mistakes, it can still be fixed by hand
+        Err(err) => {
+            println!("Not saving settings, {}", err);
+            return;
+        }
+    };
+    saved.copy_changed_settings(&config, &opened);
+    if let Err(err) = write_config(&path.0, &saved) {
+        println!("Couldn't save settings, {}", err);
+    }
+}
+
+pub struct ConfigPlugin;
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Config>()
+            .init_resource::<ConfigPath>()
+            .init_resource::<OpenedSettings>()
+            .add_system_set(
+                SystemSet::on_enter(AppState::Settings).with_system(open_settings.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Settings).with_system(save_settings.system()),
+            );
+    }
+}
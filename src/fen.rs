@@ -0,0 +1,346 @@
+use crate::pieces::*;
+
+/// Pieces on the board and the side to move, as written in FEN
+#[derive(Clone, Debug)]
+pub struct Position {
+    pub pieces: Vec<Piece>,
+    pub turn: PieceColor,
+    /// Number of the move being played, counting a move of each side as one
+    pub fullmove: u32,
+    /// Checks given by White and by Black, for Three-check
+    pub checks: [u8; 2],
+    /// Pieces White and Black hold in hand, for Crazyhouse
+    pub pockets: [Vec<PieceType>; 2],
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position {
+            pieces: starting_pieces(),
+            turn: PieceColor::White,
+            fullmove: 1,
+            checks: [0, 0],
+            pockets: Default::default(),
+        }
+    }
+}
+
+fn piece_from_char(c: char) -> Option<(PieceColor, PieceType)> {
+    let color = if c.is_ascii_uppercase() {
+        PieceColor::White
+    } else {
+        PieceColor::Black
+    };
+    let piece_type = match c.to_ascii_lowercase() {
+        'k' => PieceType::King,
+        'q' => PieceType::Queen,
+        'r' => PieceType::Rook,
+        'b' => PieceType::Bishop,
+        'n' => PieceType::Knight,
+        'p' => PieceType::Pawn,
+        _ => return None,
+    };
+    Some((color, piece_type))
+}
+
+/// Parses a FEN like `rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1`.
+/// Castling rights can also name the rook's file, as in X-FEN and Shredder-FEN. The en passant
+/// square and the halfmove clock aren't used by these rules, so they are ignored. Three-check
+/// counters can be given as the checks left, like `3+3` after the en passant square, or as the
+/// checks given, like `+0+0` at the end. Pieces in hand are written after the board, like
+/// `[Qp]`, or as a 9th rank. Promoted pieces are followed by `~`, as they go back in hand as
+/// pawns
+pub fn parse_fen(fen: &str) -> Result<Position, String> {
+    let mut fields = fen.split_whitespace();
+    let placement = fields.next().ok_or("FEN is empty")?;
+    let (placement, mut pocket) = match placement.find('[') {
+        Some(start) => (
+            &placement[..start],
+            placement[start + 1..].trim_end_matches(']'),
+        ),
+        None => (placement, ""),
+    };
+    let mut ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() == FIELD_SIZE as usize + 1 {
+        pocket = ranks.pop().unwrap_or_default();
+    }
+    let mut pockets: [Vec<PieceType>; 2] = Default::default();
+    for c in pocket.chars() {
+        let (color, piece_type) =
+            piece_from_char(c).ok_or_else(|| format!("unknown piece {} in FEN pocket", c))?;
+        match color {
+            PieceColor::White => pockets[0].push(piece_type),
+            PieceColor::Black => pockets[1].push(piece_type),
+        }
+    }
+    if ranks.len() != FIELD_SIZE as usize {
+        return Err(format!("FEN has {} ranks instead of 8", ranks.len()));
+    }
+    let mut pieces = Vec::new();
+    // The 8th rank comes first
+    for (i, rank) in ranks.iter().enumerate() {
+        let x = FIELD_SIZE - 1 - i as u8;
+        let mut y = 0;
+        for c in rank.chars() {
+            if c == '~' {
+                match pieces.last_mut() {
+                    Some(piece) if (piece.x, piece.y + 1) == (x, y) => piece.promoted = true,
+                    _ => return Err("~ in FEN doesn't follow a piece".to_string()),
+                }
+                continue;
+            }
+            if y >= FIELD_SIZE {
+                y += 1;
+                break;
+            }
+            if let Some(empty) = c.to_digit(10) {
+                y += empty as u8;
+                continue;
+            }
+            let (color, piece_type) =
+                piece_from_char(c).ok_or_else(|| format!("unknown piece {} in FEN", c))?;
+            pieces.push(Piece {
+                color,
+                piece_type,
+                x,
+                y,
+                energy: 0,
+                can_castle: false,
+                promoted: false,
+            });
+            y += 1;
+        }
+        if y != FIELD_SIZE {
+            return Err(format!("rank {} of the FEN doesn't have 8 squares", x + 1));
+        }
+    }
+    let turn = match fields.next() {
+        None | Some("w") => PieceColor::White,
+        Some("b") => PieceColor::Black,
+        Some(turn) => return Err(format!("unknown side to move {} in FEN", turn)),
+    };
+    if let Some(castling) = fields.next() {
+        parse_castling(castling, &mut pieces)?;
+    }
+    let mut rest: Vec<&str> = fields.collect();
+    let checks = match rest.iter().position(|field| field.contains('+')) {
+        Some(index) => parse_checks(rest.remove(index))?,
+        None => [0, 0],
+    };
+    let fullmove = rest
+        .get(2)
+        .and_then(|fullmove| fullmove.parse().ok())
+        .unwrap_or(1)
+        .max(1);
+    Ok(Position {
+        pieces,
+        turn,
+        fullmove,
+        checks,
+        pockets,
+    })
+}
+
+/// Checks given by each side, from `+1+0` or from the checks left like `2+3`
+fn parse_checks(field: &str) -> Result<[u8; 2], String> {
+    let invalid = || format!("invalid check counter {} in FEN", field);
+    let given = field.starts_with('+');
+    let counts: Vec<u8> = field
+        .split('+')
+        .filter(|count| !count.is_empty())
+        .map(|count| count.parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    if counts.len() != 2 {
+        return Err(invalid());
+    }
+    Ok(if given {
+        [counts[0], counts[1]]
+    } else {
+        [3u8.saturating_sub(counts[0]), 3u8.saturating_sub(counts[1])]
+    })
+}
+
+/// Lets the king and rook of each castling right castle. `K` and `Q` are the outermost rook on
+/// each side of the king, and a file letter is the rook on that file
+fn parse_castling(castling: &str, pieces: &mut [Piece]) -> Result<(), String> {
+    if castling == "-" {
+        return Ok(());
+    }
+    for c in castling.chars() {
+        let color = if c.is_ascii_uppercase() {
+            PieceColor::White
+        } else {
+            PieceColor::Black
+        };
+        let rank = back_rank(color);
+        let king = pieces
+            .iter()
+            .position(|piece| {
+                piece.color == color && piece.piece_type == PieceType::King && piece.x == rank
+            })
+            .ok_or_else(|| format!("castling right {} in FEN has no king", c))?;
+        let king_y = pieces[king].y;
+        let mut rooks = pieces.iter().enumerate().filter(|(_, piece)| {
+            piece.color == color && piece.piece_type == PieceType::Rook && piece.x == rank
+        });
+        let rook = match c.to_ascii_lowercase() {
+            'k' => rooks
+                .filter(|(_, piece)| piece.y > king_y)
+                .max_by_key(|(_, piece)| piece.y),
+            'q' => rooks
+                .filter(|(_, piece)| piece.y < king_y)
+                .min_by_key(|(_, piece)| piece.y),
+            file @ 'a'..='h' => rooks.find(|(_, piece)| piece.y == file as u8 - b'a'),
+            _ => return Err(format!("unknown castling right {} in FEN", c)),
+        }
+        .map(|(index, _)| index)
+        .ok_or_else(|| format!("castling right {} in FEN has no rook", c))?;
+        pieces[king].can_castle = true;
+        pieces[rook].can_castle = true;
+    }
+    Ok(())
+}
+
+fn back_rank(color: PieceColor) -> u8 {
+    match color {
+        PieceColor::White => 0,
+        PieceColor::Black => FIELD_SIZE - 1,
+    }
+}
+
+fn piece_char(piece: &Piece) -> char {
+    let c = match piece.piece_type {
+        PieceType::King => 'k',
+        PieceType::Queen => 'q',
+        PieceType::Rook => 'r',
+        PieceType::Bishop => 'b',
+        PieceType::Knight => 'n',
+        PieceType::Pawn => 'p',
+    };
+    match piece.color {
+        PieceColor::White => c.to_ascii_uppercase(),
+        PieceColor::Black => c,
+    }
+}
+
+/// Castling rights in X-FEN: `K` and `Q` when the rook is the outermost one on its side, which
+/// is always the case in the classic game, and the rook's file otherwise
+fn castling_rights(pieces: &[Piece]) -> String {
+    let mut rights = String::new();
+    for &color in [PieceColor::White, PieceColor::Black].iter() {
+        let rank = back_rank(color);
+        let king = match pieces.iter().find(|piece| {
+            piece.color == color
+                && piece.piece_type == PieceType::King
+                && piece.x == rank
+                && piece.can_castle
+        }) {
+            Some(king) => king,
+            None => continue,
+        };
+        let rooks: Vec<&Piece> = pieces
+            .iter()
+            .filter(|piece| {
+                piece.color == color && piece.piece_type == PieceType::Rook && piece.x == rank
+            })
+            .collect();
+        for &(letter, king_side) in [('k', true), ('q', false)].iter() {
+            let side: Vec<&Piece> = rooks
+                .iter()
+                .copied()
+                .filter(|rook| (rook.y > king.y) == king_side)
+                .collect();
+            let files = side.iter().map(|rook| rook.y);
+            let outermost = if king_side { files.max() } else { files.min() };
+            for rook in side.iter().filter(|rook| rook.can_castle) {
+                let c = if Some(rook.y) == outermost {
+                    letter
+                } else {
+                    (b'a' + rook.y) as char
+                };
+                rights.push(match color {
+                    PieceColor::White => c.to_ascii_uppercase(),
+                    PieceColor::Black => c,
+                });
+            }
+        }
+    }
+    if rights.is_empty() {
+        rights.push('-');
+    }
+    rights
+}
+
+/// Writes the position as X-FEN, without an en passant square
+pub fn to_fen(position: &Position) -> String {
+    let mut ranks = Vec::new();
+    for x in (0..FIELD_SIZE).rev() {
+        let mut rank = String::new();
+        let mut empty = 0;
+        for y in 0..FIELD_SIZE {
+            match position
+                .pieces
+                .iter()
+                .find(|piece| (piece.x, piece.y) == (x, y))
+            {
+                Some(piece) => {
+                    if empty > 0 {
+                        rank.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    rank.push(piece_char(piece));
+                    if piece.promoted {
+                        rank.push('~');
+                    }
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            rank.push_str(&empty.to_string());
+        }
+        ranks.push(rank);
+    }
+    let mut placement = ranks.join("/");
+    if position.pockets.iter().any(|pocket| !pocket.is_empty()) {
+        let pocket: String = [PieceColor::White, PieceColor::Black]
+            .iter()
+            .zip(position.pockets.iter())
+            .flat_map(|(&color, pocket)| {
+                pocket
+                    .iter()
+                    .map(move |&piece_type| piece_char(&Piece::in_pocket(color, piece_type)))
+            })
+            .collect();
+        placement.push_str(&format!("[{}]", pocket));
+    }
+    let turn = match position.turn {
+        PieceColor::White => "w",
+        PieceColor::Black => "b",
+    };
+    let mut fen = format!(
+        "{} {} {} - 0 {}",
+        placement,
+        turn,
+        castling_rights(&position.pieces),
+        position.fullmove
+    );
+    if position.checks != [0, 0] {
+        fen.push_str(&format!(" +{}+{}", position.checks[0], position.checks[1]));
+    }
+    fen
+}
+
+/// FEN of a Chess960 starting position by its number
+pub fn chess960_fen(index: u16) -> String {
+    to_fen(&Position {
+        pieces: chess960_pieces(index),
+        ..Default::default()
+    })
+}
+
+/// Position a game starts from, the usual one unless a FEN is given
+pub fn start_position(fen: Option<&str>) -> Position {
+    // FENs are checked when they are given, so this doesn't fail in practice
+    fen.and_then(|fen| parse_fen(fen).ok()).unwrap_or_default()
+}
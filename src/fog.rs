@@ -0,0 +1,207 @@
+use bevy::prelude::*;
+
+use crate::{
+    board::{GameResult, PlayerTurn, Taken},
+    headless::is_headless,
+    history::{Browse, History},
+    menu::{AppState, GameSetup, OpponentKind},
+    pieces::*,
+};
+
+/// What the player at the screen can see, in fog of war games
+#[derive(Default)]
+pub struct Fog {
+    /// Side whose view is shown, or `None` when the whole board is
+    pub viewer: Option<PieceColor>,
+    /// Squares the viewer can see
+    pub visible: Vec<(u8, u8)>,
+    /// Whether the screen stays covered until the next player of a hot-seat game takes the seat
+    pub handoff: bool,
+}
+
+impl Fog {
+    pub fn hides(&self, square: (u8, u8)) -> bool {
+        self.viewer.is_some() && !self.visible.contains(&square)
+    }
+
+    /// Whether `piece` is out of the viewer's sight. Their own pieces are always seen
+    pub fn hides_piece(&self, piece: &Piece) -> bool {
+        Some(piece.color) != self.viewer && self.hides((piece.x, piece.y))
+    }
+}
+
+/// Follows the player at the screen: the local one, or the side to move in hot-seat games
+fn update_fog(
+    setup: Res<GameSetup>,
+    turn: Res<PlayerTurn>,
+    history: Res<History>,
+    result: Res<Option<GameResult>>,
+    pieces_query: Query<&Piece, Without<Taken>>,
+    mut fog: ResMut<Fog>,
+) {
+    let hot_seat = setup.opponent == OpponentKind::Human;
+    // Everything is revealed once the game is over
+    let viewer = if result.is_some() {
+        None
+    } else if hot_seat {
+        Some(turn.0)
+    } else {
+        Some(setup.player_color)
+    };
+    let pieces: Vec<Piece> = pieces_query.iter().copied().collect();
+    let visible =
+        viewer.and_then(|viewer| history.rules().visible_squares(&pieces, &history, viewer));
+    let viewer = viewer.filter(|_| visible.is_some());
+    let visible = visible.unwrap_or_default();
+    // Only touch the resource when the view changes, so its change detection means something
+    if fog.viewer != viewer || fog.visible != visible {
+        fog.viewer = viewer;
+        fog.visible = visible;
+    }
+    if hot_seat && fog.viewer.is_some() && turn.is_changed() && !fog.handoff {
+        fog.handoff = true;
+    }
+    if fog.viewer.is_none() && fog.handoff {
+        fog.handoff = false;
+    }
+}
+
+/// Past positions would show the pieces hidden in the fog
+fn stop_browsing(fog: Res<Fog>, mut browse: ResMut<Browse>) {
+    if fog.viewer.is_some() && browse.0.is_some() {
+        browse.0 = None;
+    }
+}
+
+fn hide_fogged_pieces(
+    fog: Res<Fog>,
+    browse: Res<Browse>,
+    pieces_query: Query<(&Piece, &Children)>,
+    mut visible_query: Query<&mut Visible>,
+) {
+    if !fog.is_changed() && fog.viewer.is_none() {
+        return;
+    }
+    for (piece, children) in pieces_query.iter() {
+        let is_visible = browse.0.is_none() && !fog.handoff && !fog.hides_piece(piece);
+        for child in children.iter() {
+            if let Ok(mut visible) = visible_query.get_mut(*child) {
+                if visible.is_visible != is_visible {
+                    visible.is_visible = is_visible;
+                }
+            }
+        }
+    }
+}
+
+/// Screen covering the board between the turns of a hot-seat game
+struct HandoffScreen;
+
+struct HandoffMaterials {
+    background: Handle<ColorMaterial>,
+    font: Handle<Font>,
+}
+
+impl FromWorld for HandoffMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        HandoffMaterials {
+            background: materials.add(Color::rgb(0.02, 0.02, 0.02).into()),
+            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        }
+    }
+}
+
+fn show_handoff_screen(
+    mut commands: Commands,
+    materials: Res<HandoffMaterials>,
+    fog: Res<Fog>,
+    turn: Res<PlayerTurn>,
+    query: Query<Entity, With<HandoffScreen>>,
+) {
+    if !fog.is_changed() {
+        return;
+    }
+    let shown = query.iter().next();
+    match (fog.handoff, shown) {
+        (true, None) => {
+            let text = format!(
+                "{} to play, click or press Space to continue",
+                match turn.0 {
+                    PieceColor::White => "White",
+                    PieceColor::Black => "Black",
+                }
+            );
+            commands
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                        position_type: PositionType::Absolute,
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    material: materials.background.clone(),
+                    ..Default::default()
+                })
+                .insert(HandoffScreen)
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            text,
+                            TextStyle {
+                                font: materials.font.clone(),
+                                font_size: 40.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    });
+                });
+        }
+        (false, Some(entity)) => commands.entity(entity).despawn_recursive(),
+        _ => {}
+    }
+}
+
+/// The next player takes the seat with a click or Space. Runs before the board, which doesn't
+/// get to see the clicks and keys pressed during the handoff
+fn end_handoff(
+    mut mouse_button_inputs: ResMut<Input<MouseButton>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut fog: ResMut<Fog>,
+) {
+    if !fog.handoff {
+        return;
+    }
+    if mouse_button_inputs.just_pressed(MouseButton::Left)
+        || keyboard_input.just_pressed(KeyCode::Space)
+    {
+        fog.handoff = false;
+    }
+    mouse_button_inputs.reset(MouseButton::Left);
+    keyboard_input.reset(KeyCode::Return);
+    keyboard_input.reset(KeyCode::Space);
+}
+
+pub struct FogPlugin;
+impl Plugin for FogPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Fog>()
+            .add_system(update_fog.system().after("add_turn_to_history"))
+            .add_system(stop_browsing.system());
+        if is_headless(app) {
+            return;
+        }
+        app.init_resource::<HandoffMaterials>()
+            .add_system(hide_fogged_pieces.system())
+            .add_system(show_handoff_screen.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(end_handoff.system().before("select_square")),
+            );
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    ai,
+    annotations::Annotations,
+    board::{GameResult, MovePieceEvent, PlayerTurn, Taken},
+    history::History,
+    menu::{AppState, GameSetup},
+    notation::parse_move,
+    pgn::export_pgn,
+    pieces::Piece,
+};
+
+/// Present when running without a window. Plugins leave out their rendering and input systems,
+/// and the sides played from this computer are moved by a script or the built-in AI
+pub struct Headless {
+    /// Moves still to be played, in SAN or coordinates
+    pub moves: VecDeque<String>,
+    /// Plies after which the game is stopped, as it might never end otherwise
+    pub max_plies: usize,
+}
+
+impl Default for Headless {
+    fn default() -> Self {
+        Headless {
+            moves: VecDeque::new(),
+            max_plies: 500,
+        }
+    }
+}
+
+pub fn is_headless(app: &mut AppBuilder) -> bool {
+    app.world().contains_resource::<Headless>()
+}
+
+/// Plays the next scripted move, or the one the AI picks once the script is over
+fn play_local_side(
+    mut headless: ResMut<Headless>,
+    setup: Res<GameSetup>,
+    turn: Res<PlayerTurn>,
+    history: Res<History>,
+    pieces_query: Query<&Piece, Without<Taken>>,
+    mut move_piece_w: EventWriter<MovePieceEvent>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    if !setup.is_local(turn.0) {
+        return;
+    }
+    let pieces: Vec<Piece> = pieces_query.iter().copied().collect();
+    let mv = match headless.moves.pop_front() {
+        Some(text) => match parse_move(&pieces, &history, turn.0, &text) {
+            Some(event) => Some(event),
+            None => {
+                println!("Invalid scripted move {}", text);
+                app_exit_events.send(AppExit);
+                return;
+            }
+        },
+        None => ai::best_move(&pieces, &history, turn.0, setup.ai_depth).map(|(from, to)| {
+            MovePieceEvent {
+                from,
+                to,
+                promotion: None,
+            }
+        }),
+    };
+    match mv {
+        Some(event) => move_piece_w.send(event),
+        None => {
+            println!("No moves left to play");
+            app_exit_events.send(AppExit);
+        }
+    }
+}
+
+fn print_pgn(history: &History, setup: &GameSetup, result: Option<&GameResult>) {
+    println!(
+        "{}",
+        export_pgn(history, setup, result, &Annotations::default())
+    );
+}
+
+fn stop_after_max_plies(
+    headless: Res<Headless>,
+    history: Res<History>,
+    setup: Res<GameSetup>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    if history.is_changed() && history.turns.len() >= headless.max_plies {
+        println!("Stopping after {} plies", headless.max_plies);
+        print_pgn(&history, &setup, None);
+        app_exit_events.send(AppExit);
+    }
+}
+
+fn finish_game(
+    history: Res<History>,
+    setup: Res<GameSetup>,
+    result: Res<Option<GameResult>>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    print_pgn(&history, &setup, result.as_ref());
+    app_exit_events.send(AppExit);
+}
+
+/// Plays a game to the end without a window, printing it as PGN
+pub struct HeadlessPlugin;
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(play_local_side.system().before("move_piece"))
+                .with_system(stop_after_max_plies.system().after("add_turn_to_history")),
+        )
+        .add_system_set(SystemSet::on_enter(AppState::GameOver).with_system(finish_game.system()));
+    }
+}
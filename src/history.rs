@@ -1,16 +1,154 @@
 use std::fmt::Display;
 
 use bevy::prelude::{AppBuilder, Plugin};
-use bevy::prelude::{EventReader, IntoSystem, ResMut};
+use bevy::prelude::{EventReader, IntoSystem, ParallelSystemDescriptorCoercion, ResMut};
+use serde::{Deserialize, Serialize};
 
-use crate::pieces::{PieceColor, PieceType};
+use crate::{
+    fen::{start_position, Position},
+    pieces::{dropped_piece, king_attackers, promotes, Piece, PieceColor, PieceType},
+    variant::{GameVariant, Variant},
+};
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(from = "SavedHistory")]
 pub struct History {
     pub turns: Vec<Turn>,
+    /// FEN of the position the game started from, when it isn't the usual one
+    fen: Option<String>,
+    pub variant: GameVariant,
+    /// Position parsed from `fen`, which is looked at for every move played or searched
+    #[serde(skip)]
+    start: Position,
 }
 
-#[derive(Clone, Copy, Debug)]
+/// History as it is saved, without the starting position parsed from the FEN
+#[derive(Deserialize)]
+struct SavedHistory {
+    turns: Vec<Turn>,
+    #[serde(default)]
+    fen: Option<String>,
+    #[serde(default)]
+    variant: GameVariant,
+}
+
+impl From<SavedHistory> for History {
+    fn from(saved: SavedHistory) -> Self {
+        History {
+            turns: saved.turns,
+            ..History::new(saved.fen, saved.variant)
+        }
+    }
+}
+
+impl History {
+    /// History of a game starting from `fen`, or from the usual position when it's `None`
+    pub fn new(fen: Option<String>, variant: GameVariant) -> Self {
+        History {
+            turns: Vec::new(),
+            start: start_position(fen.as_deref()),
+            fen,
+            variant,
+        }
+    }
+
+    pub fn fen(&self) -> Option<&str> {
+        self.fen.as_deref()
+    }
+
+    pub fn start(&self) -> &Position {
+        &self.start
+    }
+
+    pub fn rules(&self) -> &'static dyn Variant {
+        self.variant.rules()
+    }
+
+    /// History of the same game with no turns played yet
+    pub fn restarted(&self) -> History {
+        History {
+            turns: Vec::new(),
+            fen: self.fen.clone(),
+            variant: self.variant,
+            start: self.start.clone(),
+        }
+    }
+
+    /// Checks `color` has given, counting those before the starting position
+    pub fn checks_given(&self, color: PieceColor) -> u8 {
+        let before = match color {
+            PieceColor::White => self.start().checks[0],
+            PieceColor::Black => self.start().checks[1],
+        };
+        let given = self
+            .turns
+            .iter()
+            .filter(|turn| turn.color == color && turn.check)
+            .count();
+        before + given as u8
+    }
+
+    /// Pieces `color` holds in hand, for drop variants
+    pub fn pocket(&self, color: PieceColor) -> Vec<PieceType> {
+        let pockets = &self.start().pockets;
+        let mut pocket = match color {
+            PieceColor::White => pockets[0].clone(),
+            PieceColor::Black => pockets[1].clone(),
+        };
+        for turn in self.turns.iter().filter(|turn| turn.color == color) {
+            if let Some(captured) = turn.captured {
+                pocket.push(if turn.captured_promoted {
+                    PieceType::Pawn
+                } else {
+                    captured
+                });
+            }
+            if turn.is_drop() {
+                if let Some(index) = pocket.iter().position(|&held| held == turn.piece_type) {
+                    pocket.remove(index);
+                }
+            }
+        }
+        pocket
+    }
+
+    /// Piece a move from `from` plays: the one on that square, or one in hand for a drop
+    pub fn piece_on(&self, pieces: &[Piece], from: (u8, u8)) -> Option<Piece> {
+        match dropped_piece(from) {
+            Some((color, piece_type)) => Some(Piece::in_pocket(color, piece_type))
+                .filter(|_| self.pocket(color).contains(&piece_type)),
+            None => pieces
+                .iter()
+                .find(|piece| (piece.x, piece.y) == from)
+                .copied(),
+        }
+    }
+
+    /// Pieces on the board after the first `moves` turns
+    pub fn position_after(&self, moves: usize) -> Vec<Piece> {
+        let mut pieces = self.start().pieces.clone();
+        let mut played = self.restarted();
+        let rules = self.rules();
+        for turn in self.turns.iter().take(moves) {
+            let from = (turn.from_x, turn.from_y);
+            let mv = played.piece_on(&pieces, from).and_then(|piece| {
+                rules.find_move(&piece, &pieces, &played, (turn.to_x, turn.to_y))
+            });
+            if let Some(mv) = mv {
+                rules.play(&mut pieces, from, mv, turn.promotion);
+            }
+            played.turns.push(*turn);
+        }
+        pieces
+    }
+}
+
+/// Index of the turn being looked at in the move list, or `None` while following the game
+#[derive(Default)]
+pub struct Browse(pub Option<usize>);
+
+/// A move, or a drop when `from` is a pocket square
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Turn {
     pub color: PieceColor,
     pub piece_type: PieceType,
@@ -18,6 +156,55 @@ pub struct Turn {
     pub from_y: u8,
     pub to_x: u8,
     pub to_y: u8,
+    /// Whether the move put the other king in check
+    #[serde(default)]
+    pub check: bool,
+    /// Type of the piece taken, which goes in hand in drop variants
+    #[serde(default)]
+    pub captured: Option<PieceType>,
+    /// Whether the piece taken was a promoted pawn, which goes in hand as a pawn
+    #[serde(default)]
+    pub captured_promoted: bool,
+    /// Piece the pawn became on the last rank
+    #[serde(default)]
+    pub promotion: Option<PieceType>,
+}
+
+impl Turn {
+    /// Move of `piece` to `to`, which removed the pieces in `removed` and left `after`
+    pub fn new(
+        piece: &Piece,
+        to: (u8, u8),
+        removed: &[Piece],
+        after: &[Piece],
+        history: &History,
+    ) -> Self {
+        let captured = removed.iter().find(|other| other.color != piece.color);
+        Turn {
+            color: piece.color,
+            piece_type: piece.piece_type,
+            from_x: piece.x,
+            from_y: piece.y,
+            to_x: to.0,
+            to_y: to.1,
+            check: !king_attackers(after, history, piece.color.opposite()).is_empty(),
+            captured: captured.map(|other| other.piece_type),
+            captured_promoted: captured.map_or(false, |other| other.promoted),
+            promotion: Some(piece)
+                .filter(|piece| promotes(piece, to))
+                .and_then(|piece| {
+                    after
+                        .iter()
+                        .find(|other| (other.x, other.y) == to && other.color == piece.color)
+                })
+                .map(|other| other.piece_type),
+        }
+    }
+
+    /// Whether a piece in hand was put on the board
+    pub fn is_drop(&self) -> bool {
+        dropped_piece((self.from_x, self.from_y)).is_some()
+    }
 }
 
 impl Display for Turn {
@@ -58,7 +245,14 @@ impl Plugin for HistoryPlugin {
     fn build(&self, app: &mut AppBuilder) {
         // app.add_startup_system(create_history.system());
         app.init_resource::<History>()
+            .init_resource::<Browse>()
             .add_event::<Turn>()
-            .add_system(add_turn_to_history.system());
+            // Record the turn in the same frame it is played, so anything saving the game sees it
+            .add_system(
+                add_turn_to_history
+                    .system()
+                    .after("move_piece")
+                    .label("add_turn_to_history"),
+            );
     }
 }
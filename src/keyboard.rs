@@ -0,0 +1,177 @@
+use bevy::prelude::*;
+
+use crate::{
+    board::{MovePieceEvent, PlayerTurn, Taken},
+    camera::BoardView,
+    history::{Browse, History},
+    menu::{AppState, GameSetup},
+    notation::parse_move,
+    pieces::*,
+};
+
+/// Square picked with Enter, shown once an arrow key is pressed
+#[derive(Default)]
+pub struct KeyboardCursor(pub Option<(u8, u8)>);
+
+/// Move being typed, in SAN like `Nf3` or coordinates like `g1f3`
+#[derive(Default)]
+pub struct MoveInput {
+    pub text: String,
+    /// Why the last move typed wasn't played
+    error: Option<String>,
+}
+
+struct MoveInputText;
+
+/// Arrow keys move the cursor, up being away from the side the board is looked from
+fn move_cursor(
+    keyboard_input: Res<Input<KeyCode>>,
+    turn: Res<PlayerTurn>,
+    view: Res<BoardView>,
+    mut cursor: ResMut<KeyboardCursor>,
+) {
+    let (dx, dy) = if keyboard_input.just_pressed(KeyCode::Up) {
+        (1, 0)
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        (-1, 0)
+    } else if keyboard_input.just_pressed(KeyCode::Right) {
+        (0, 1)
+    } else if keyboard_input.just_pressed(KeyCode::Left) {
+        (0, -1)
+    } else {
+        return;
+    };
+    let (dx, dy) = match view.side {
+        PieceColor::White => (dx, dy),
+        PieceColor::Black => (-dx, -dy),
+    };
+    cursor.0 = Some(match cursor.0 {
+        Some((x, y)) => (
+            (x as i8 + dx).clamp(0, 7) as u8,
+            (y as i8 + dy).clamp(0, 7) as u8,
+        ),
+        // Start in front of the king of the player on turn
+        None => match turn.0 {
+            PieceColor::White => (1, 4),
+            PieceColor::Black => (6, 4),
+        },
+    });
+}
+
+/// Whether `c` is kept when typing a move, like the dashes of `O-O` or the `@` of a drop
+pub fn is_move_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '@'
+}
+
+/// Types a move, which is played on Enter if it's valid
+#[allow(clippy::too_many_arguments)]
+fn type_move(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut received_characters: EventReader<ReceivedCharacter>,
+    turn: Res<PlayerTurn>,
+    setup: Res<GameSetup>,
+    browse: Res<Browse>,
+    history: Res<History>,
+    pieces_query: Query<&Piece, Without<Taken>>,
+    mut move_input: ResMut<MoveInput>,
+    mut move_piece: EventWriter<MovePieceEvent>,
+) {
+    let typed: Vec<char> = received_characters
+        .iter()
+        .map(|event| event.char)
+        .filter(|&c| is_move_char(c))
+        .collect();
+    let erase = keyboard_input.just_pressed(KeyCode::Back);
+    let submit = keyboard_input.just_pressed(KeyCode::Return);
+    let clear = keyboard_input.just_pressed(KeyCode::Escape);
+    if typed.is_empty() && !erase && !(submit && !move_input.text.is_empty()) && !clear {
+        return;
+    }
+    let move_input = &mut *move_input;
+    move_input.error = None;
+    move_input.text.extend(typed);
+    if erase {
+        move_input.text.pop();
+    }
+    if clear {
+        move_input.text.clear();
+    }
+    if !submit || move_input.text.is_empty() {
+        return;
+    }
+
+    let text = std::mem::take(&mut move_input.text);
+    if browse.0.is_some() || !setup.is_local(turn.0) {
+        move_input.error = Some("Not your turn".to_string());
+        return;
+    }
+    let pieces: Vec<Piece> = pieces_query.iter().copied().collect();
+    match parse_move(&pieces, &history, turn.0, &text) {
+        Some(event) => move_piece.send(event),
+        None => move_input.error = Some(format!("Invalid move {}", text)),
+    }
+}
+
+fn init_move_input_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::rgb(0.8, 0.8, 0.8),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(MoveInputText);
+}
+
+fn move_input_text_update(
+    move_input: Res<MoveInput>,
+    mut query: Query<&mut Text, With<MoveInputText>>,
+) {
+    if !move_input.is_changed() {
+        return;
+    }
+    let value = match &move_input.error {
+        Some(error) => error.clone(),
+        None if move_input.text.is_empty() => String::new(),
+        None => format!("Move: {}_", move_input.text),
+    };
+    for mut text in query.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+pub struct KeyboardPlugin;
+impl Plugin for KeyboardPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<KeyboardCursor>()
+            .init_resource::<MoveInput>()
+            .add_startup_system(init_move_input_text.system())
+            .add_system(move_input_text_update.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(move_cursor.system())
+                    // Enter only picks the cursor square when no move is being typed
+                    .with_system(
+                        type_move
+                            .system()
+                            .after("select_square")
+                            .before("move_piece"),
+                    ),
+            );
+    }
+}
@@ -0,0 +1,47 @@
+use bevy::{app::PluginGroupBuilder, prelude::*};
+
+pub mod ai;
+pub mod annotations;
+pub mod board;
+pub mod camera;
+pub mod captured;
+pub mod cli;
+pub mod clock;
+pub mod combust;
+pub mod config;
+pub mod fen;
+pub mod fog;
+pub mod headless;
+pub mod history;
+pub mod keyboard;
+pub mod menu;
+pub mod network;
+pub mod notation;
+pub mod opponent;
+pub mod pgn;
+pub mod pieces;
+pub mod save;
+pub mod theme;
+pub mod uci;
+pub mod ui;
+pub mod variant;
+
+/// Rules and state of the game, needed with or without a window. Without one, insert
+/// `headless::Headless` first so the plugins leave out their rendering and input systems
+pub struct GamePlugins;
+impl PluginGroup for GamePlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group
+            .add(config::ConfigPlugin)
+            .add(cli::CliPlugin)
+            .add(menu::MenuPlugin)
+            .add(board::BoardPlugin)
+            .add(pieces::PiecesPlugin)
+            .add(history::HistoryPlugin)
+            .add(captured::CapturedPlugin)
+            .add(clock::ClockPlugin)
+            .add(fog::FogPlugin)
+            .add(opponent::OpponentPlugin)
+            .add(save::SavePlugin);
+    }
+}
@@ -5,59 +5,85 @@ use bevy::{
     prelude::*,
 };
 use bevy_mod_picking::*;
+use structopt::{clap, StructOpt};
 
-mod pieces;
-use pieces::*;
-mod board;
-use board::*;
-mod ui;
-use ui::*;
-
-use crate::{combust::CombustPlugin, history::HistoryPlugin};
-mod combust;
-mod history;
+use bevy_chess::{
+    annotations::AnnotationsPlugin,
+    camera::{BoardCamera, CameraPlugin, Orbit},
+    cli::{Args, Launch, PendingLaunch},
+    combust::CombustPlugin,
+    config::{default_config_path, load_config, ConfigPath},
+    headless::HeadlessPlugin,
+    keyboard::KeyboardPlugin,
+    theme::ThemePlugin,
+    ui::UIPlugin,
+    GamePlugins,
+};
 
 fn main() {
-    App::build()
-        // Set antialiasing to use 4 samples
-        .insert_resource(Msaa { samples: 8 })
-        // Set WindowDescriptor Resource to change title and size
-        .insert_resource(WindowDescriptor {
-            title: "Chess!".to_string(),
-            width: 1000.,
-            height: 1000.,
-            ..Default::default()
-        })
-        .add_plugins(DefaultPlugins)
-        .init_resource::<PickingCamera>()
-        .add_plugin(PickingPlugin)
-        .add_plugin(BoardPlugin)
-        .add_plugin(PiecesPlugin)
-        .add_plugin(HistoryPlugin)
-        .add_plugin(CombustPlugin)
-        .add_plugin(UIPlugin)
-        .add_plugin(DiagnosticsPlugin)
-        // .add_plugin(FrameTimeDiagnosticsPlugin)
-        // .add_plugin(LogDiagnosticsPlugin {
-        //     debug: false,
-        //     wait_duration: Duration::from_secs(1),
-        //     filter: Some(vec![FrameTimeDiagnosticsPlugin::FPS]),
-        // })
-        .add_startup_system(setup.system())
-        .run();
+    let args = Args::from_args();
+    let config_path = args.config.clone().unwrap_or_else(default_config_path);
+    let mut config = load_config(&config_path);
+    args.apply(&mut config);
+    let mut game_setup = config.game_setup();
+    // Without a window there is no menu to start a game from
+    let launch = match args.launch(&mut game_setup) {
+        Ok(None) if args.headless => Some(Launch::New),
+        Ok(launch) => launch,
+        Err(err) => clap::Error::with_description(&err, clap::ErrorKind::InvalidValue).exit(),
+    };
+
+    let mut app = App::build();
+    app
+        // Set antialiasing, window title and size before the window is created
+        .insert_resource(config.msaa())
+        .insert_resource(config.window_descriptor())
+        .insert_resource(config)
+        .insert_resource(ConfigPath(config_path))
+        // Options of the first game, from the config and the command line
+        .insert_resource(game_setup)
+        .insert_resource(PendingLaunch(launch));
+    if args.headless {
+        // Before the other plugins, which leave out their rendering when it's there
+        app.insert_resource(args.headless())
+            .add_plugins(MinimalPlugins)
+            .add_plugin(HeadlessPlugin);
+    } else {
+        app.add_plugins(DefaultPlugins)
+            .init_resource::<PickingCamera>()
+            .add_plugin(PickingPlugin)
+            // Before the plugins building their materials from the theme
+            .add_plugin(ThemePlugin);
+    }
+    app.add_plugins(GamePlugins);
+    if !args.headless {
+        app.add_plugin(KeyboardPlugin)
+            .add_plugin(CameraPlugin)
+            .add_plugin(AnnotationsPlugin)
+            .add_plugin(CombustPlugin)
+            .add_plugin(UIPlugin)
+            .add_plugin(DiagnosticsPlugin)
+            // .add_plugin(FrameTimeDiagnosticsPlugin)
+            // .add_plugin(LogDiagnosticsPlugin {
+            //     debug: false,
+            //     wait_duration: Duration::from_secs(1),
+            //     filter: Some(vec![FrameTimeDiagnosticsPlugin::FPS]),
+            // })
+            .add_startup_system(setup.system());
+    }
+    app.run();
 }
 
 fn setup(mut commands: Commands) {
     commands
         // Camera
         .spawn_bundle(PerspectiveCameraBundle {
-            transform: Transform::from_matrix(Mat4::from_rotation_translation(
-                Quat::from_xyzw(-0.3, -0.5, -0.3, 0.5).normalize(),
-                Vec3::new(-2.5, 12.0, 4.0),
-            )),
+            transform: Orbit::default().transform(),
             ..Default::default()
         })
         .insert_bundle(PickingCameraBundle::default())
+        .insert(BoardCamera)
+        .insert(Orbit::default())
         // Light
         .commands()
         .spawn_bundle(LightBundle {
@@ -0,0 +1,750 @@
+use bevy::{app::AppExit, ecs::schedule::ShouldRun, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    annotations::Annotations,
+    board::{EndReason, GameResult, NewGameEvent},
+    clock::{format_clock, Clocks, TimeControl, TIME_CONTROLS},
+    config::Config,
+    headless::is_headless,
+    history::History,
+    pgn::{export_pgn, write_pgn},
+    pieces::PieceColor,
+    save::{LastGame, LoadGameEvent},
+    theme::Themes,
+    variant::GameVariant,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AppState {
+    MainMenu,
+    /// Shown at startup instead of the main menu when the last game wasn't finished
+    ContinuePrompt,
+    Setup,
+    InGame,
+    GameOver,
+    /// Pushed over the main menu or the game, which continue where they were once it's closed
+    Settings,
+}
+
+/// Runs the systems playing the game, which goes on while the settings are open over it. Unlike
+/// an `on_update` and an `on_inactive_update` set, the systems are only added once, so their
+/// event readers don't read the same events again when the settings are opened or closed
+pub fn game_running(state: Res<State<AppState>>) -> ShouldRun {
+    if *state.current() == AppState::InGame || state.inactives().contains(&AppState::InGame) {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// Who plays the side that isn't controlled from this window
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum OpponentKind {
+    Human,
+    Ai,
+    Engine,
+    Host,
+    Connect,
+}
+
+impl OpponentKind {
+    pub const ALL: [OpponentKind; 5] = [
+        OpponentKind::Human,
+        OpponentKind::Ai,
+        OpponentKind::Engine,
+        OpponentKind::Host,
+        OpponentKind::Connect,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpponentKind::Human => "Human",
+            OpponentKind::Ai => "Built-in AI",
+            OpponentKind::Engine => "UCI engine",
+            OpponentKind::Host => "Network (host)",
+            OpponentKind::Connect => "Network (join)",
+        }
+    }
+}
+
+/// Everything chosen before starting a game
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameSetup {
+    pub opponent: OpponentKind,
+    /// Color played from this window, when the opponent isn't a local human
+    pub player_color: PieceColor,
+    pub time_control: Option<TimeControl>,
+    pub variant: GameVariant,
+    /// Whether pieces gain energy when taking other pieces
+    pub energy: bool,
+    pub ai_depth: u8,
+    pub engine_path: String,
+    pub address: String,
+    /// Position to start from instead of the usual one, in FEN
+    pub fen: Option<String>,
+    /// Number of the Chess960 starting position, random when missing
+    pub chess960_position: Option<u16>,
+}
+
+impl Default for GameSetup {
+    fn default() -> Self {
+        GameSetup {
+            opponent: OpponentKind::Human,
+            player_color: PieceColor::White,
+            time_control: None,
+            variant: GameVariant::ClassicFun,
+            energy: true,
+            ai_depth: 3,
+            engine_path: "stockfish".to_string(),
+            address: "127.0.0.1:7878".to_string(),
+            fen: None,
+            chess960_position: None,
+        }
+    }
+}
+
+impl GameSetup {
+    /// Whether the pieces of `color` are moved by someone in front of this window
+    pub fn is_local(&self, color: PieceColor) -> bool {
+        self.opponent == OpponentKind::Human || color == self.player_color
+    }
+
+    /// FEN of the position to start from, or `None` for the usual one. A random Chess960 position
+    /// is picked on each call
+    pub fn start_fen(&self) -> Option<String> {
+        self.fen
+            .clone()
+            .or_else(|| self.variant.rules().start_fen(self))
+    }
+}
+
+/// Returns the element after `current` in `all`, wrapping around
+fn cycle<T: PartialEq + Copy>(all: &[T], current: T) -> T {
+    let index = all.iter().position(|&x| x == current).unwrap_or(0);
+    all[(index + 1) % all.len()]
+}
+
+/// Goes through the time controls, from unlimited to the longest one
+fn next_time_control(time_control: Option<TimeControl>) -> Option<TimeControl> {
+    match time_control {
+        None => Some(TIME_CONTROLS[0]),
+        Some(time_control) => {
+            let index = TIME_CONTROLS.iter().position(|&tc| tc == time_control);
+            index.and_then(|index| TIME_CONTROLS.get(index + 1).copied())
+        }
+    }
+}
+
+fn time_control_text(time_control: Option<TimeControl>) -> String {
+    match time_control {
+        Some(time_control) => time_control.to_string(),
+        None => "Unlimited".to_string(),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum SetupOption {
+    Opponent,
+    Color,
+    TimeControl,
+    Variant,
+    Energy,
+    AiDepth,
+    /// Engine path or network address, edited by typing
+    Address,
+}
+
+impl SetupOption {
+    const ALL: [SetupOption; 7] = [
+        SetupOption::Opponent,
+        SetupOption::Color,
+        SetupOption::TimeControl,
+        SetupOption::Variant,
+        SetupOption::Energy,
+        SetupOption::AiDepth,
+        SetupOption::Address,
+    ];
+
+    fn text(&self, setup: &GameSetup) -> String {
+        match self {
+            SetupOption::Opponent => format!("Opponent: {}", setup.opponent.name()),
+            SetupOption::Color => format!(
+                "Play as: {}",
+                match setup.player_color {
+                    PieceColor::White => "White",
+                    PieceColor::Black => "Black",
+                }
+            ),
+            SetupOption::TimeControl => {
+                format!("Time control: {}", time_control_text(setup.time_control))
+            }
+            SetupOption::Variant => match (setup.variant, setup.chess960_position) {
+                (GameVariant::Chess960, Some(index)) => format!("Variant: Chess960 #{}", index),
+                (variant, _) => format!("Variant: {}", variant.name()),
+            },
+            SetupOption::Energy => format!(
+                "Energy on kill: {}",
+                if setup.energy { "On" } else { "Off" }
+            ),
+            SetupOption::AiDepth => format!("AI depth: {}", setup.ai_depth),
+            SetupOption::Address => match setup.opponent {
+                OpponentKind::Engine => format!("Engine: {}_", setup.engine_path),
+                OpponentKind::Host | OpponentKind::Connect => {
+                    format!("Address: {}_", setup.address)
+                }
+                _ => "-".to_string(),
+            },
+        }
+    }
+
+    fn next(&self, setup: &mut GameSetup) {
+        match self {
+            SetupOption::Opponent => setup.opponent = cycle(&OpponentKind::ALL, setup.opponent),
+            SetupOption::Color => setup.player_color = setup.player_color.opposite(),
+            SetupOption::TimeControl => setup.time_control = next_time_control(setup.time_control),
+            SetupOption::Variant => {
+                setup.variant = cycle(&GameVariant::ALL, setup.variant);
+                setup.chess960_position = None;
+            }
+            SetupOption::Energy => setup.energy = !setup.energy,
+            SetupOption::AiDepth => setup.ai_depth = setup.ai_depth % 4 + 1,
+            // Edited with the keyboard instead
+            SetupOption::Address => {}
+        }
+    }
+}
+
+const ANIMATION_SPEEDS: [f32; 4] = [4., 8., 16., 32.];
+const MSAA_SAMPLES: [u32; 4] = [1, 2, 4, 8];
+
+/// Preferences changed from the settings menu, and saved to the config file
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum SettingsOption {
+    Theme,
+    AnimationSpeed,
+    Antialiasing,
+    TimeControl,
+    AutoQueen,
+}
+
+impl SettingsOption {
+    const ALL: [SettingsOption; 5] = [
+        SettingsOption::Theme,
+        SettingsOption::AnimationSpeed,
+        SettingsOption::Antialiasing,
+        SettingsOption::TimeControl,
+        SettingsOption::AutoQueen,
+    ];
+
+    fn text(&self, config: &Config) -> String {
+        match self {
+            SettingsOption::Theme => format!("Theme: {}", config.theme),
+            SettingsOption::AnimationSpeed => {
+                format!("Animation speed: {}", config.animation_speed)
+            }
+            // Pipelines are only built with the samples set at startup
+            SettingsOption::Antialiasing => format!(
+                "Antialiasing: {}x (after restart)",
+                config.window.msaa_samples
+            ),
+            SettingsOption::TimeControl => format!(
+                "Default time control: {}",
+                time_control_text(config.time_control())
+            ),
+            SettingsOption::AutoQueen => format!(
+                "Auto-queen: {}",
+                if config.game.auto_queen { "On" } else { "Off" }
+            ),
+        }
+    }
+
+    fn next(&self, config: &mut Config, themes: &mut Themes) {
+        match self {
+            SettingsOption::Theme => {
+                themes.next();
+                config.theme = themes.current().name.clone();
+            }
+            SettingsOption::AnimationSpeed => {
+                config.animation_speed = cycle(&ANIMATION_SPEEDS, config.animation_speed)
+            }
+            SettingsOption::Antialiasing => {
+                config.window.msaa_samples = cycle(&MSAA_SAMPLES, config.window.msaa_samples)
+            }
+            SettingsOption::TimeControl => {
+                let time_control = next_time_control(config.time_control());
+                config.set_time_control(time_control);
+            }
+            SettingsOption::AutoQueen => config.game.auto_queen = !config.game.auto_queen,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum MenuButton {
+    NewGame,
+    Continue,
+    Settings,
+    Quit,
+    Start,
+    Back,
+    Option(SetupOption),
+    Rematch,
+    ExportPgn,
+    MainMenu,
+    Setting(SettingsOption),
+    CloseSettings,
+}
+
+/// Root of the menu currently on screen
+struct MenuRoot;
+
+/// Text of a setup option button
+struct SetupOptionText(SetupOption);
+
+/// Text of a settings option button
+struct SettingsOptionText(SettingsOption);
+
+struct MenuMaterials {
+    background: Handle<ColorMaterial>,
+    button: Handle<ColorMaterial>,
+    hovered: Handle<ColorMaterial>,
+    pressed: Handle<ColorMaterial>,
+    font: Handle<Font>,
+}
+
+impl FromWorld for MenuMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        MenuMaterials {
+            background: materials.add(Color::rgba(0., 0.05, 0.05, 0.8).into()),
+            button: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
+            hovered: materials.add(Color::rgb(0.25, 0.25, 0.25).into()),
+            pressed: materials.add(Color::rgb(0.35, 0.75, 0.35).into()),
+            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        }
+    }
+}
+
+fn spawn_menu_root(
+    commands: &mut Commands,
+    materials: &MenuMaterials,
+    spawn_children: impl FnOnce(&mut ChildBuilder),
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.background.clone(),
+            ..Default::default()
+        })
+        .insert(MenuRoot)
+        .with_children(spawn_children);
+}
+
+fn spawn_text(parent: &mut ChildBuilder, materials: &MenuMaterials, text: &str, font_size: f32) {
+    parent.spawn_bundle(TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(font_size / 3.)),
+            ..Default::default()
+        },
+        text: Text::with_section(
+            text,
+            TextStyle {
+                font: materials.font.clone(),
+                font_size,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    });
+}
+
+fn spawn_title(parent: &mut ChildBuilder, materials: &MenuMaterials, title: &str) {
+    spawn_text(parent, materials, title, 60.0);
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    materials: &MenuMaterials,
+    button: MenuButton,
+    label: &str,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(400.), Val::Px(50.)),
+                margin: Rect::all(Val::Px(5.)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.button.clone(),
+            ..Default::default()
+        })
+        .insert(button)
+        .with_children(|parent| {
+            let mut text = parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    label,
+                    TextStyle {
+                        font: materials.font.clone(),
+                        font_size: 25.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+            match button {
+                MenuButton::Option(option) => {
+                    text.insert(SetupOptionText(option));
+                }
+                MenuButton::Setting(option) => {
+                    text.insert(SettingsOptionText(option));
+                }
+                _ => {}
+            }
+        });
+}
+
+fn spawn_main_menu(
+    mut commands: Commands,
+    materials: Res<MenuMaterials>,
+    last_game: Res<LastGame>,
+) {
+    spawn_menu_root(&mut commands, &materials, |parent| {
+        spawn_title(parent, &materials, "Chess!");
+        spawn_button(parent, &materials, MenuButton::NewGame, "New game");
+        if last_game.0.is_some() {
+            spawn_button(
+                parent,
+                &materials,
+                MenuButton::Continue,
+                "Continue last game",
+            );
+        }
+        spawn_button(parent, &materials, MenuButton::Settings, "Settings");
+        spawn_button(parent, &materials, MenuButton::Quit, "Quit");
+    });
+}
+
+fn spawn_continue_prompt(
+    mut commands: Commands,
+    materials: Res<MenuMaterials>,
+    last_game: Res<LastGame>,
+) {
+    let save = match &last_game.0 {
+        Some(save) => save,
+        None => return,
+    };
+    spawn_menu_root(&mut commands, &materials, |parent| {
+        spawn_title(parent, &materials, "Continue last game?");
+        let moves = (save.history.turns.len() + 1) / 2;
+        spawn_text(
+            parent,
+            &materials,
+            &format!(
+                "{}, {} move{}, {} to play",
+                save.setup.variant.name(),
+                moves,
+                if moves == 1 { "" } else { "s" },
+                match save.turn {
+                    PieceColor::White => "White",
+                    PieceColor::Black => "Black",
+                }
+            ),
+            30.0,
+        );
+        spawn_button(parent, &materials, MenuButton::Continue, "Continue");
+        spawn_button(parent, &materials, MenuButton::MainMenu, "Not now");
+    });
+}
+
+fn spawn_settings_menu(mut commands: Commands, materials: Res<MenuMaterials>, config: Res<Config>) {
+    spawn_menu_root(&mut commands, &materials, |parent| {
+        spawn_title(parent, &materials, "Settings");
+        for option in SettingsOption::ALL.iter() {
+            spawn_button(
+                parent,
+                &materials,
+                MenuButton::Setting(*option),
+                &option.text(&config),
+            );
+        }
+        spawn_button(parent, &materials, MenuButton::CloseSettings, "Back");
+    });
+}
+
+fn spawn_setup_menu(mut commands: Commands, materials: Res<MenuMaterials>, setup: Res<GameSetup>) {
+    spawn_menu_root(&mut commands, &materials, |parent| {
+        spawn_title(parent, &materials, "New game");
+        for option in SetupOption::ALL.iter() {
+            spawn_button(
+                parent,
+                &materials,
+                MenuButton::Option(*option),
+                &option.text(&setup),
+            );
+        }
+        spawn_button(parent, &materials, MenuButton::Start, "Start");
+        spawn_button(parent, &materials, MenuButton::Back, "Back");
+    });
+}
+
+fn spawn_game_over_menu(
+    mut commands: Commands,
+    materials: Res<MenuMaterials>,
+    result: Res<Option<GameResult>>,
+    history: Res<History>,
+    clocks: Res<Clocks>,
+) {
+    spawn_menu_root(&mut commands, &materials, |parent| {
+        if let Some(result) = result.as_ref() {
+            let title = match result.winner {
+                Some(PieceColor::White) => "White won!",
+                Some(PieceColor::Black) => "Black won!",
+                None => "Draw!",
+            };
+            spawn_title(parent, &materials, title);
+            let reason = match result.reason {
+                EndReason::KingTaken => "The king has been taken",
+                EndReason::KingExploded => "The king has been blown up",
+                // The side that ran out of time loses
+                EndReason::Timeout => match result.winner {
+                    Some(PieceColor::White) => "Black ran out of time",
+                    Some(PieceColor::Black) => "White ran out of time",
+                    None => "Time ran out",
+                },
+                EndReason::Checkmate => "Checkmate",
+                EndReason::Stalemate => "Stalemate",
+                EndReason::HillReached => "The king has reached the hill",
+                EndReason::ThreeChecks => "Three checks have been given",
+                EndReason::RaceWon => "The race to the last rank is over",
+                EndReason::PiecesGone => "All the pieces have been given away",
+                EndReason::HordeTaken => "The whole horde has been taken",
+            };
+            spawn_text(parent, &materials, reason, 30.0);
+        }
+        let moves = (history.turns.len() + 1) / 2;
+        spawn_text(
+            parent,
+            &materials,
+            &format!(
+                "{} move{} in {}",
+                moves,
+                if moves == 1 { "" } else { "s" },
+                format_clock(clocks.elapsed)
+            ),
+            30.0,
+        );
+        spawn_button(
+            parent,
+            &materials,
+            MenuButton::Rematch,
+            "Rematch with colors swapped",
+        );
+        spawn_button(parent, &materials, MenuButton::ExportPgn, "Export PGN");
+        spawn_button(parent, &materials, MenuButton::MainMenu, "Main menu");
+    });
+}
+
+fn despawn_menu(mut commands: Commands, query: Query<Entity, With<MenuRoot>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn menu_buttons(
+    materials: Res<MenuMaterials>,
+    mut state: ResMut<State<AppState>>,
+    mut setup: ResMut<GameSetup>,
+    mut last_game: ResMut<LastGame>,
+    history: Res<History>,
+    result: Res<Option<GameResult>>,
+    annotations: Res<Annotations>,
+    mut config: ResMut<Config>,
+    mut themes: ResMut<Themes>,
+    mut new_game_w: EventWriter<NewGameEvent>,
+    mut load_game_w: EventWriter<LoadGameEvent>,
+    mut app_exit_events: EventWriter<AppExit>,
+    mut query: Query<(&Interaction, &MenuButton, &mut Handle<ColorMaterial>), Changed<Interaction>>,
+) {
+    for (interaction, button, mut material) in query.iter_mut() {
+        *material = match interaction {
+            Interaction::Clicked => materials.pressed.clone(),
+            Interaction::Hovered => materials.hovered.clone(),
+            Interaction::None => materials.button.clone(),
+        };
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        match button {
+            MenuButton::NewGame => {
+                // Positions given on the command line are only for the games started there
+                setup.fen = None;
+                state.set(AppState::Setup).unwrap()
+            }
+            MenuButton::Continue => {
+                if let Some(save) = last_game.0.take() {
+                    load_game_w.send(LoadGameEvent(save));
+                    state.set(AppState::InGame).unwrap();
+                }
+            }
+            MenuButton::Settings => state.push(AppState::Settings).unwrap(),
+            MenuButton::Quit => app_exit_events.send(AppExit),
+            MenuButton::Start => {
+                new_game_w.send(NewGameEvent);
+                state.set(AppState::InGame).unwrap();
+            }
+            MenuButton::Back => state.set(AppState::MainMenu).unwrap(),
+            MenuButton::Option(option) => option.next(&mut setup),
+            MenuButton::Rematch => {
+                setup.player_color = setup.player_color.opposite();
+                new_game_w.send(NewGameEvent);
+                state.set(AppState::InGame).unwrap();
+            }
+            MenuButton::ExportPgn => {
+                let pgn = export_pgn(&history, &setup, result.as_ref(), &annotations);
+                match write_pgn(&pgn) {
+                    Ok(path) => println!("Game exported to {}", path.display()),
+                    Err(err) => println!("Couldn't export game, {}", err),
+                }
+            }
+            MenuButton::MainMenu => state.set(AppState::MainMenu).unwrap(),
+            MenuButton::Setting(option) => {
+                option.next(&mut config, &mut themes);
+                // The game being set up starts from the new default too
+                if *option == SettingsOption::TimeControl {
+                    setup.time_control = config.time_control();
+                }
+            }
+            MenuButton::CloseSettings => state.pop().unwrap(),
+        }
+    }
+}
+
+/// Types the engine path or network address
+fn edit_setup_address(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut received_characters: EventReader<ReceivedCharacter>,
+    mut setup: ResMut<GameSetup>,
+) {
+    let typed: Vec<char> = received_characters
+        .iter()
+        .map(|event| event.char)
+        .filter(|c| !c.is_control())
+        .collect();
+    let erase = keyboard_input.just_pressed(KeyCode::Back);
+    if typed.is_empty() && !erase {
+        return;
+    }
+    let setup = &mut *setup;
+    let text = match setup.opponent {
+        OpponentKind::Engine => &mut setup.engine_path,
+        OpponentKind::Host | OpponentKind::Connect => &mut setup.address,
+        _ => return,
+    };
+    text.extend(typed);
+    if erase {
+        text.pop();
+    }
+}
+
+fn update_setup_texts(setup: Res<GameSetup>, mut query: Query<(&mut Text, &SetupOptionText)>) {
+    if !setup.is_changed() {
+        return;
+    }
+    for (mut text, SetupOptionText(option)) in query.iter_mut() {
+        text.sections[0].value = option.text(&setup);
+    }
+}
+
+fn update_settings_texts(config: Res<Config>, mut query: Query<(&mut Text, &SettingsOptionText)>) {
+    if !config.is_changed() {
+        return;
+    }
+    for (mut text, SettingsOptionText(option)) in query.iter_mut() {
+        text.sections[0].value = option.text(&config);
+    }
+}
+
+/// F10 opens the settings during a game, Escape closes them
+fn settings_keys(keyboard_input: Res<Input<KeyCode>>, mut state: ResMut<State<AppState>>) {
+    match state.current() {
+        AppState::InGame if keyboard_input.just_pressed(KeyCode::F10) => {
+            state.push(AppState::Settings).unwrap()
+        }
+        AppState::Settings if keyboard_input.just_pressed(KeyCode::Escape) => state.pop().unwrap(),
+        _ => {}
+    }
+}
+
+pub struct MenuPlugin;
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<GameSetup>()
+            .add_state(AppState::MainMenu);
+        if is_headless(app) {
+            return;
+        }
+        app.init_resource::<MenuMaterials>()
+            .add_system(menu_buttons.system())
+            .add_system(settings_keys.system())
+            .add_system_set(
+                SystemSet::on_enter(AppState::MainMenu).with_system(spawn_main_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::MainMenu).with_system(despawn_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_pause(AppState::MainMenu).with_system(despawn_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_resume(AppState::MainMenu).with_system(spawn_main_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::ContinuePrompt)
+                    .with_system(spawn_continue_prompt.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::ContinuePrompt).with_system(despawn_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Settings).with_system(spawn_settings_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Settings)
+                    .with_system(update_settings_texts.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Settings).with_system(despawn_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Setup).with_system(spawn_setup_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Setup)
+                    .with_system(edit_setup_address.system())
+                    .with_system(update_setup_texts.system()),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Setup).with_system(despawn_menu.system()))
+            .add_system_set(
+                SystemSet::on_enter(AppState::GameOver).with_system(spawn_game_over_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::GameOver).with_system(despawn_menu.system()),
+            );
+    }
+}
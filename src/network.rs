@@ -0,0 +1,226 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{pieces::PieceColor, variant::GameVariant};
+
+/// Line sent between the players of a network game. At the start of each game the host greets
+/// the other player with the variant, the position and then the color they play
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    Variant(GameVariant),
+    /// FEN of the starting position, or `None` for the usual one
+    Position(Option<String>),
+    /// Color of the player receiving it, which ends the greeting
+    Color(PieceColor),
+    /// Move in UCI coordinates
+    Move(String),
+}
+
+impl Message {
+    pub fn parse(line: &str) -> Option<Self> {
+        let (kind, rest) = line.trim().split_once(' ')?;
+        let rest = rest.trim();
+        Some(match kind {
+            "variant" => Message::Variant(
+                *GameVariant::ALL
+                    .iter()
+                    .find(|variant| variant.name() == rest)?,
+            ),
+            "position" => match rest.strip_prefix("fen ") {
+                Some(fen) => Message::Position(Some(fen.trim().to_string())),
+                None if rest == "startpos" => Message::Position(None),
+                None => return None,
+            },
+            "color" => match rest {
+                "white" => Message::Color(PieceColor::White),
+                "black" => Message::Color(PieceColor::Black),
+                _ => return None,
+            },
+            "move" if !rest.contains(char::is_whitespace) => Message::Move(rest.to_string()),
+            _ => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Message::Variant(variant) => write!(f, "variant {}", variant.name()),
+            Message::Position(Some(fen)) => write!(f, "position fen {}", fen),
+            Message::Position(None) => write!(f, "position startpos"),
+            Message::Color(PieceColor::White) => write!(f, "color white"),
+            Message::Color(PieceColor::Black) => write!(f, "color black"),
+            Message::Move(mv) => write!(f, "move {}", mv),
+        }
+    }
+}
+
+enum PeerEvent {
+    Connected(TcpStream),
+    Line(String),
+    Disconnected(String),
+}
+
+/// How often the host checks whether the game was left while waiting for an opponent
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Connection to another instance of the game, exchanging one message per line. Dropping it
+/// closes the connection, or stops waiting for one
+pub struct Peer {
+    writer: Option<TcpStream>,
+    /// Messages sent before the connection was made
+    outbox: Vec<Message>,
+    events: Mutex<Receiver<PeerEvent>>,
+    /// Tells the thread making the connection that it isn't wanted anymore
+    closed: Arc<AtomicBool>,
+}
+
+impl Peer {
+    /// Waits for another player to connect to `address`
+    pub fn host(address: &str) -> Self {
+        let address = address.to_string();
+        Peer::start(move |closed| {
+            let listener = TcpListener::bind(&address)?;
+            println!("Waiting for an opponent on {}", address);
+            // Polled, so the port is freed once the game is left
+            listener.set_nonblocking(true)?;
+            loop {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        stream.set_nonblocking(false)?;
+                        return Ok(stream);
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        if closed.load(Ordering::Relaxed) {
+                            return Err(io::Error::new(
+                                io::ErrorKind::Interrupted,
+                                "stopped waiting for an opponent",
+                            ));
+                        }
+                        thread::sleep(ACCEPT_INTERVAL);
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        })
+    }
+
+    pub fn connect(address: &str) -> Self {
+        let address = address.to_string();
+        Peer::start(move |_| TcpStream::connect(&address))
+    }
+
+    fn start(open: impl FnOnce(&AtomicBool) -> io::Result<TcpStream> + Send + 'static) -> Self {
+        let (sender, receiver) = channel();
+        let closed = Arc::new(AtomicBool::new(false));
+        let thread_closed = closed.clone();
+        thread::spawn(move || {
+            let stream = match open(&thread_closed) {
+                Ok(stream) => stream,
+                Err(err) => {
+                    let _ = sender.send(PeerEvent::Disconnected(err.to_string()));
+                    return;
+                }
+            };
+            let writer = match stream.try_clone() {
+                Ok(writer) => writer,
+                Err(err) => {
+                    let _ = sender.send(PeerEvent::Disconnected(err.to_string()));
+                    return;
+                }
+            };
+            let _ = sender.send(PeerEvent::Connected(writer));
+            // Dropped meanwhile, after looking for the stream in the events it was sent with
+            if thread_closed.load(Ordering::Relaxed) {
+                let _ = stream.shutdown(Shutdown::Both);
+                return;
+            }
+            for line in BufReader::new(stream).lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(PeerEvent::Line(line)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+            let _ = sender.send(PeerEvent::Disconnected("connection closed".to_string()));
+        });
+        Peer {
+            writer: None,
+            outbox: Vec::new(),
+            events: Mutex::new(receiver),
+            closed,
+        }
+    }
+
+    pub fn send(&mut self, message: Message) {
+        match self.writer.as_mut() {
+            Some(writer) => {
+                if let Err(err) = writeln!(writer, "{}", message) {
+                    println!("Couldn't send to opponent, {}", err);
+                }
+            }
+            None => self.outbox.push(message),
+        }
+    }
+
+    /// Returns the messages received since the last poll. Lines that aren't messages are
+    /// reported and skipped
+    pub fn poll(&mut self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        loop {
+            let event = match self.events.lock().unwrap().try_recv() {
+                Ok(event) => event,
+                Err(_) => break,
+            };
+            match event {
+                PeerEvent::Connected(writer) => {
+                    println!("Opponent connected");
+                    self.writer = Some(writer);
+                    for message in std::mem::take(&mut self.outbox) {
+                        self.send(message);
+                    }
+                }
+                PeerEvent::Line(line) => match Message::parse(&line) {
+                    Some(message) => messages.push(message),
+                    None => println!("Unknown message from opponent: {}", line),
+                },
+                PeerEvent::Disconnected(reason) => {
+                    println!("Opponent disconnected, {}", reason);
+                    self.writer = None;
+                }
+            }
+        }
+        messages
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        // Also ends the thread reading from the stream, and lets the other player know
+        let connected = self
+            .events
+            .lock()
+            .unwrap()
+            .try_iter()
+            .find_map(|event| match event {
+                PeerEvent::Connected(writer) => Some(writer),
+                _ => None,
+            });
+        for writer in self.writer.iter().chain(connected.iter()) {
+            let _ = writer.shutdown(Shutdown::Both);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{
+    board::MovePieceEvent,
+    history::{History, Turn},
+    pieces::*,
+    variant::movable_pieces,
+};
+
+/// Name of a square, like `e4`. `x` is the rank and `y` the file
+pub fn square_name(x: u8, y: u8) -> String {
+    format!("{}{}", (b'a' + y) as char, x + 1)
+}
+
+/// Parses a square name like `e4` into `(x, y)`
+pub fn parse_square(name: &str) -> Option<(u8, u8)> {
+    let mut chars = name.chars();
+    let file = chars.next()?;
+    let rank = chars.next()?;
+    if chars.next().is_some() || !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+        return None;
+    }
+    Some((rank as u8 - b'1', file as u8 - b'a'))
+}
+
+/// Move in UCI coordinates, like `e2e4` or `e7e8q` with a promotion, or a drop like `N@f3`
+pub fn uci_move(from: (u8, u8), to: (u8, u8), promotion: Option<PieceType>) -> String {
+    match dropped_piece(from) {
+        Some((_, piece_type)) => format!("{}@{}", drop_letter(piece_type), square_name(to.0, to.1)),
+        None => {
+            let mut uci = format!("{}{}", square_name(from.0, from.1), square_name(to.0, to.1));
+            uci.extend(
+                promotion
+                    .and_then(piece_letter)
+                    .map(|c| c.to_ascii_lowercase()),
+            );
+            uci
+        }
+    }
+}
+
+/// Letter of a piece dropped from the hand, where pawns have one too
+fn drop_letter(piece_type: PieceType) -> char {
+    piece_letter(piece_type).unwrap_or('P')
+}
+
+/// Type of the piece written with `letter`, which pawns don't have
+fn letter_type(letter: char) -> Option<PieceType> {
+    PIECE_TYPES
+        .iter()
+        .copied()
+        .find(|&piece_type| piece_letter(piece_type) == Some(letter))
+}
+
+/// Parses a move of `color` in UCI coordinates, where a trailing letter is the piece a pawn
+/// promotes to. Drops like `N@f3` come from the pocket square of the piece
+pub fn parse_uci_move(text: &str, color: PieceColor) -> Option<MovePieceEvent> {
+    let text = text.trim();
+    if !(text.len() == 4 || text.len() == 5) || !text.is_ascii() {
+        return None;
+    }
+    if let Some(square) = text.strip_prefix(|c: char| c.is_ascii_uppercase()) {
+        let square = square.strip_prefix('@')?;
+        let letter = text.chars().next()?;
+        let piece_type = *PIECE_TYPES
+            .iter()
+            .find(|&&piece_type| drop_letter(piece_type) == letter)?;
+        return Some(MovePieceEvent {
+            from: pocket_square(color, piece_type),
+            to: parse_square(square)?,
+            promotion: None,
+        });
+    }
+    let from = parse_square(&text[0..2])?;
+    let to = parse_square(&text[2..4])?;
+    let promotion = match text[4..].chars().next() {
+        Some(letter) => Some(letter_type(letter.to_ascii_uppercase())?),
+        None => None,
+    };
+    Some(MovePieceEvent {
+        from,
+        to,
+        promotion,
+    })
+}
+
+impl Turn {
+    pub fn uci(&self) -> String {
+        uci_move(
+            (self.from_x, self.from_y),
+            (self.to_x, self.to_y),
+            self.promotion,
+        )
+    }
+}
+
+pub fn piece_letter(piece_type: PieceType) -> Option<char> {
+    match piece_type {
+        PieceType::King => Some('K'),
+        PieceType::Queen => Some('Q'),
+        PieceType::Rook => Some('R'),
+        PieceType::Bishop => Some('B'),
+        PieceType::Knight => Some('N'),
+        PieceType::Pawn => None,
+    }
+}
+
+/// Standard Algebraic Notation of a move, like `Nbd2`, `exd5` or `e8=Q`. A pawn reaching the
+/// last rank becomes `promotion`, or the first piece the rules promote to
+pub fn san(
+    pieces: &[Piece],
+    history: &History,
+    piece: &Piece,
+    mv: MovePosition,
+    promotion: Option<PieceType>,
+) -> String {
+    let (x, y, takeable) = mv;
+    if dropped_piece((piece.x, piece.y)).is_some() {
+        return format!("{}@{}", drop_letter(piece.piece_type), square_name(x, y));
+    }
+    if is_castling(pieces, (piece.x, piece.y), (x, y)) {
+        let san = if y > piece.y { "O-O" } else { "O-O-O" };
+        return san.to_string();
+    }
+    let mut san = String::new();
+    match piece_letter(piece.piece_type) {
+        None => {
+            if takeable.is_some() {
+                san.push((b'a' + piece.y) as char);
+            }
+        }
+        Some(letter) => {
+            san.push(letter);
+            // Disambiguate from the other pieces of the same type that could move there too
+            let others: Vec<&Piece> = pieces
+                .iter()
+                .filter(|other| {
+                    other.color == piece.color
+                        && other.piece_type == piece.piece_type
+                        && (other.x, other.y) != (piece.x, piece.y)
+                        && history
+                            .rules()
+                            .moves(other, pieces, history)
+                            .iter()
+                            .any(|&(other_x, other_y, _)| (other_x, other_y) == (x, y))
+                })
+                .collect();
+            if !others.is_empty() {
+                if others.iter().all(|other| other.y != piece.y) {
+                    san.push((b'a' + piece.y) as char);
+                } else if others.iter().all(|other| other.x != piece.x) {
+                    san.push((b'1' + piece.x) as char);
+                } else {
+                    san.push_str(&square_name(piece.x, piece.y));
+                }
+            }
+        }
+    }
+    if takeable.is_some() {
+        san.push('x');
+    }
+    san.push_str(&square_name(x, y));
+    if promotes(piece, (x, y)) {
+        let piece_type = promotion.unwrap_or(history.rules().promotion_types()[0]);
+        san.push('=');
+        san.extend(piece_letter(piece_type));
+    }
+    san
+}
+
+/// Drops the capture, promotion and check marks, so `exd5+` and `ed5` are the same move, like
+/// `e8=Q` and `e8Q`. Castling can be written with zeros too
+fn strip_san(text: &str) -> String {
+    text.chars()
+        .filter(|c| !matches!(c, 'x' | '=' | '+' | '#' | '!' | '?'))
+        .map(|c| if c == '0' { 'O' } else { c })
+        .collect()
+}
+
+/// Finds the move of `color` written in SAN
+pub fn parse_san(
+    pieces: &[Piece],
+    history: &History,
+    color: PieceColor,
+    text: &str,
+) -> Option<MovePieceEvent> {
+    let text = strip_san(text.trim());
+    let rules = history.rules();
+    movable_pieces(rules, pieces, history, color)
+        .into_iter()
+        .flat_map(|piece| {
+            rules
+                .moves(&piece, pieces, history)
+                .into_iter()
+                .map(move |mv| (piece, mv))
+        })
+        .flat_map(|(piece, mv)| {
+            // A pawn reaching the last rank is written with the piece it becomes
+            let promotions: Vec<Option<PieceType>> = if promotes(&piece, (mv.0, mv.1)) {
+                rules.promotion_types().into_iter().map(Some).collect()
+            } else {
+                vec![None]
+            };
+            promotions
+                .into_iter()
+                .map(move |promotion| (piece, mv, promotion))
+        })
+        .find(|(piece, mv, promotion)| {
+            strip_san(&san(pieces, history, piece, *mv, *promotion)) == text
+        })
+        .map(|(piece, (x, y, _), promotion)| MovePieceEvent {
+            from: (piece.x, piece.y),
+            to: (x, y),
+            promotion,
+        })
+}
+
+/// Move of `color` written in coordinates like `g1f3` or in SAN like `Nf3`, if it's valid
+pub fn parse_move(
+    pieces: &[Piece],
+    history: &History,
+    color: PieceColor,
+    text: &str,
+) -> Option<MovePieceEvent> {
+    // Coordinates still have to be a valid move
+    parse_uci_move(text, color)
+        .filter(|event| {
+            history
+                .piece_on(pieces, event.from)
+                .filter(|piece| piece.color == color)
+                .and_then(|piece| history.rules().find_move(&piece, pieces, history, event.to))
+                .is_some()
+        })
+        .or_else(|| parse_san(pieces, history, color, text))
+}
+
+/// SAN of every turn in the history, replaying the game from the starting position
+pub fn san_moves(history: &History) -> Vec<String> {
+    let mut pieces = history.start().pieces.clone();
+    let mut played = history.restarted();
+    let rules = history.rules();
+    let mut moves = Vec::new();
+    for turn in history.turns.iter() {
+        let from = (turn.from_x, turn.from_y);
+        let piece = played.piece_on(&pieces, from);
+        let mv = piece
+            .and_then(|piece| rules.find_move(&piece, &pieces, &played, (turn.to_x, turn.to_y)));
+        match (piece, mv) {
+            (Some(piece), Some(mv)) => {
+                moves.push(san(&pieces, &played, &piece, mv, turn.promotion));
+                rules.play(&mut pieces, from, mv, turn.promotion);
+            }
+            // The history doesn't match the position, so fall back to coordinates
+            _ => moves.push(turn.uci()),
+        }
+        played.turns.push(*turn);
+    }
+    moves
+}
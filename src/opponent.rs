@@ -0,0 +1,299 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use futures_lite::future;
+
+use crate::{
+    ai,
+    board::{MovePieceEvent, NewGameEvent, PlayerTurn, Taken},
+    headless::Headless,
+    history::{History, Turn},
+    menu::{game_running, AppState, GameSetup, OpponentKind},
+    network::{Message, Peer},
+    notation::parse_uci_move,
+    pieces::{Piece, PieceColor},
+    uci::Engine,
+};
+
+/// How long the engine gets to think about each move
+const ENGINE_MOVETIME: Duration = Duration::from_millis(1000);
+/// Small pause before the built-in AI moves, so its moves can be followed
+const AI_DELAY: f32 = 0.5;
+
+enum Connection {
+    Ai,
+    Engine(Engine),
+    Network(Peer),
+}
+
+/// The opponent playing the non local side, and what it was last asked for
+struct Opponent {
+    /// Kind of opponent the connection was made for
+    kind: Option<OpponentKind>,
+    connection: Option<Connection>,
+    /// Length of the history when the opponent was last asked to move
+    asked_at: Option<usize>,
+    /// Moves received from the network that haven't been played yet
+    received: Vec<MovePieceEvent>,
+    /// Whether the host has told the other player how the current game is played
+    greeted: bool,
+    /// Move being searched by the built-in AI, away from the frames being drawn
+    search: Option<Task<Option<MovePieceEvent>>>,
+    delay: Timer,
+}
+
+impl Default for Opponent {
+    fn default() -> Self {
+        Opponent {
+            kind: None,
+            connection: None,
+            asked_at: None,
+            received: Vec::new(),
+            greeted: false,
+            search: None,
+            delay: Timer::from_seconds(AI_DELAY, false),
+        }
+    }
+}
+
+/// Whether a game joined over the network is still waiting for the host to say which side is
+/// played here. No move is played until then
+#[derive(Default)]
+pub struct AwaitingHost(pub bool);
+
+fn connect_opponent(
+    setup: Res<GameSetup>,
+    mut opponent: ResMut<Opponent>,
+    mut awaiting_host: ResMut<AwaitingHost>,
+) {
+    opponent.asked_at = None;
+    opponent.received.clear();
+    opponent.greeted = false;
+    opponent.search = None;
+    // The host greets again for each game, as it may start from another position
+    awaiting_host.0 = setup.opponent == OpponentKind::Connect;
+    // Keep the engine or connection around when playing again
+    if opponent.kind == Some(setup.opponent) {
+        return;
+    }
+    opponent.kind = Some(setup.opponent);
+    opponent.connection = match setup.opponent {
+        OpponentKind::Human => None,
+        OpponentKind::Ai => Some(Connection::Ai),
+        OpponentKind::Engine => match Engine::spawn(&setup.engine_path) {
+            Ok(engine) => Some(Connection::Engine(engine)),
+            Err(err) => {
+                println!(
+                    "Couldn't start engine {}, {}. Using the built-in AI instead",
+                    setup.engine_path, err
+                );
+                Some(Connection::Ai)
+            }
+        },
+        OpponentKind::Host => Some(Connection::Network(Peer::host(&setup.address))),
+        OpponentKind::Connect => Some(Connection::Network(Peer::connect(&setup.address))),
+    };
+}
+
+/// Starts looking for the best move of `color` on the task pool, as deep searches take seconds
+fn start_search(
+    pool: &AsyncComputeTaskPool,
+    pieces: Vec<Piece>,
+    history: History,
+    color: PieceColor,
+    depth: u8,
+) -> Task<Option<MovePieceEvent>> {
+    pool.spawn(async move {
+        ai::best_move(&pieces, &history, color, depth).map(|(from, to)| MovePieceEvent {
+            from,
+            to,
+            promotion: None,
+        })
+    })
+}
+
+fn disconnect_opponent(mut opponent: ResMut<Opponent>, mut awaiting_host: ResMut<AwaitingHost>) {
+    *opponent = Opponent::default();
+    awaiting_host.0 = false;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn play_opponent_move(
+    time: Res<Time>,
+    pool: Res<AsyncComputeTaskPool>,
+    headless: Option<Res<Headless>>,
+    mut setup: ResMut<GameSetup>,
+    turn: Res<PlayerTurn>,
+    history: Res<History>,
+    mut opponent: ResMut<Opponent>,
+    mut awaiting_host: ResMut<AwaitingHost>,
+    pieces_query: Query<&Piece, Without<Taken>>,
+    mut move_piece_w: EventWriter<MovePieceEvent>,
+    mut new_game_w: EventWriter<NewGameEvent>,
+) {
+    let opponent = &mut *opponent;
+
+    // Network messages are read even when it's our turn, to keep the connection going
+    if let Some(Connection::Network(peer)) = opponent.connection.as_mut() {
+        // Runs after new_game, so the history is the one of the game being started
+        if setup.opponent == OpponentKind::Host && !opponent.greeted {
+            opponent.greeted = true;
+            peer.send(Message::Variant(history.variant));
+            peer.send(Message::Position(history.fen().map(str::to_string)));
+            peer.send(Message::Color(setup.player_color.opposite()));
+        }
+        for message in peer.poll() {
+            match message {
+                Message::Variant(variant) => setup.variant = variant,
+                Message::Position(fen) => setup.fen = fen,
+                Message::Move(mv) => match parse_uci_move(&mv, setup.player_color.opposite()) {
+                    Some(mv) => opponent.received.push(mv),
+                    None => println!("Opponent sent an invalid move: {}", mv),
+                },
+                Message::Color(color) => {
+                    setup.player_color = color;
+                    awaiting_host.0 = false;
+                    new_game_w.send(NewGameEvent);
+                }
+            }
+        }
+    }
+
+    if awaiting_host.0 || setup.is_local(turn.0) {
+        return;
+    }
+    let pieces: Vec<Piece> = pieces_query.iter().copied().collect();
+    let moves_played = history.turns.len();
+    if opponent.asked_at != Some(moves_played) {
+        opponent.asked_at = Some(moves_played);
+        opponent.delay.reset();
+        if let Some(Connection::Engine(engine)) = opponent.connection.as_mut() {
+            let moves: Vec<String> = history.turns.iter().map(Turn::uci).collect();
+            if let Err(err) = engine.go(history.fen(), &moves, ENGINE_MOVETIME) {
+                println!("Engine stopped working, {}", err);
+                opponent.connection = Some(Connection::Ai);
+            }
+        }
+        if let Some(Connection::Ai) = opponent.connection {
+            opponent.search = Some(start_search(
+                &pool,
+                pieces.clone(),
+                history.clone(),
+                turn.0,
+                setup.ai_depth,
+            ));
+        }
+    }
+
+    if let Some(search) = opponent.search.as_mut() {
+        // Nobody is watching games played without a window
+        let mv = if headless.is_some() {
+            future::block_on(search)
+        } else if opponent.delay.tick(time.delta()).finished() {
+            match future::block_on(future::poll_once(search)) {
+                Some(mv) => mv,
+                None => return,
+            }
+        } else {
+            return;
+        };
+        opponent.search = None;
+        if let Some(event) = mv {
+            move_piece_w.send(event);
+        }
+        return;
+    }
+
+    let mv = match opponent.connection.as_mut() {
+        Some(Connection::Engine(engine)) => {
+            let best_move = if let Some(best_move) = engine.best_move() {
+                best_move
+            } else {
+                return;
+            };
+            match parse_uci_move(&best_move, turn.0).filter(|event| {
+                history
+                    .piece_on(&pieces, event.from)
+                    .filter(|piece| piece.color == turn.0)
+                    .and_then(|piece| {
+                        history
+                            .rules()
+                            .find_move(&piece, &pieces, &history, event.to)
+                    })
+                    .is_some()
+            }) {
+                Some(event) => Some(event),
+                // Engines know nothing about our rules, so they can pick moves that aren't valid here
+                None => {
+                    println!(
+                        "Engine move {} isn't valid here, using the built-in AI instead",
+                        best_move
+                    );
+                    opponent.search = Some(start_search(
+                        &pool,
+                        pieces,
+                        history.clone(),
+                        turn.0,
+                        setup.ai_depth,
+                    ));
+                    None
+                }
+            }
+        }
+        Some(Connection::Network(_)) => {
+            if opponent.received.is_empty() {
+                return;
+            }
+            Some(opponent.received.remove(0))
+        }
+        Some(Connection::Ai) | None => return,
+    };
+    if let Some(event) = mv {
+        move_piece_w.send(event);
+    }
+}
+
+/// Lets the network opponent know about the moves played here
+fn send_local_moves(
+    setup: Res<GameSetup>,
+    mut turn_event_r: EventReader<Turn>,
+    mut opponent: ResMut<Opponent>,
+) {
+    for turn in turn_event_r.iter() {
+        if !setup.is_local(turn.color) {
+            continue;
+        }
+        if let Some(Connection::Network(peer)) = opponent.connection.as_mut() {
+            peer.send(Message::Move(turn.uci()));
+        }
+    }
+}
+
+pub struct OpponentPlugin;
+impl Plugin for OpponentPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Opponent>()
+            .init_resource::<AwaitingHost>()
+            .add_system(send_local_moves.system())
+            .add_system_set(
+                SystemSet::on_enter(AppState::InGame).with_system(connect_opponent.system()),
+            )
+            // Their clock is running while the settings are open, so they can still move
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(game_running.system())
+                    .with_system(
+                        play_opponent_move
+                            .system()
+                            .after("new_game")
+                            .before("move_piece"),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::MainMenu).with_system(disconnect_opponent.system()),
+            );
+    }
+}
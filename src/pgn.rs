@@ -0,0 +1,249 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    annotations::Annotations,
+    board::{EndReason, GameResult, MovePieceEvent},
+    fen::parse_fen,
+    history::{History, Turn},
+    menu::GameSetup,
+    notation::{parse_san, san_moves},
+    pieces::PieceColor,
+    save::save_dir,
+    variant::GameVariant,
+};
+
+/// PGN lines shouldn't be longer than this
+const LINE_LENGTH: usize = 80;
+
+/// Today's date as `YYYY.MM.DD`
+fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() / 86_400)
+        .unwrap_or(0) as i64;
+    // Days since the epoch to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+fn player_name(setup: &GameSetup, color: PieceColor) -> String {
+    if setup.is_local(color) {
+        "Player".to_string()
+    } else {
+        setup.opponent.name().to_string()
+    }
+}
+
+pub fn result_tag(result: Option<&GameResult>) -> &'static str {
+    match result.map(|result| result.winner) {
+        Some(Some(PieceColor::White)) => "1-0",
+        Some(Some(PieceColor::Black)) => "0-1",
+        Some(None) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+/// Exports the game as PGN, with the moves in SAN and the annotations as comments
+pub fn export_pgn(
+    history: &History,
+    setup: &GameSetup,
+    result: Option<&GameResult>,
+    annotations: &Annotations,
+) -> String {
+    let result_tag = result_tag(result);
+    let mut pgn = String::new();
+    let tags = [
+        ("Event", "Casual game".to_string()),
+        ("Site", "Bevy Chess".to_string()),
+        ("Date", today()),
+        ("Round", "-".to_string()),
+        ("White", player_name(setup, PieceColor::White)),
+        ("Black", player_name(setup, PieceColor::Black)),
+        ("Result", result_tag.to_string()),
+    ];
+    for (name, value) in tags.iter() {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+    }
+    if let Some(variant) = history.rules().pgn_name() {
+        pgn.push_str(&format!("[Variant \"{}\"]\n", variant));
+    }
+    // Games that don't start from the usual position
+    if let Some(fen) = history.fen() {
+        pgn.push_str("[SetUp \"1\"]\n");
+        pgn.push_str(&format!("[FEN \"{}\"]\n", fen));
+    }
+    if let Some(result) = result {
+        let termination = match result.reason {
+            EndReason::KingTaken
+            | EndReason::KingExploded
+            | EndReason::Checkmate
+            | EndReason::Stalemate
+            | EndReason::HillReached
+            | EndReason::ThreeChecks
+            | EndReason::RaceWon
+            | EndReason::PiecesGone
+            | EndReason::HordeTaken => "normal",
+            EndReason::Timeout => "time forfeit",
+        };
+        pgn.push_str(&format!("[Termination \"{}\"]\n", termination));
+    }
+    pgn.push('\n');
+
+    // Movetext, wrapped at the line length
+    let mut tokens = Vec::new();
+    // Comments go after the move leading to the annotated position. Returns whether there was one
+    let push_comment = |tokens: &mut Vec<String>, ply: usize| {
+        let position = annotations.0.get(&ply).filter(|p| !p.is_empty());
+        if let Some(position) = position {
+            tokens.push(position.pgn_comment());
+        }
+        position.is_some()
+    };
+    let mut commented = push_comment(&mut tokens, 0);
+    let start = history.start();
+    // Plies are counted from White's move, even when Black moves first
+    let first_ply = match start.turn {
+        PieceColor::White => 0,
+        PieceColor::Black => 1,
+    };
+    for (i, san) in san_moves(history).into_iter().enumerate() {
+        let ply = first_ply + i;
+        let number = start.fullmove as usize + ply / 2;
+        if ply % 2 == 0 {
+            tokens.push(format!("{}.", number));
+        } else if i == 0 || commented {
+            // Black's move is numbered again after a comment
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(san);
+        commented = push_comment(&mut tokens, i + 1);
+    }
+    tokens.push(result_tag.to_string());
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > LINE_LENGTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+    pgn
+}
+
+/// Reads the moves of the first game in a PGN, starting from its `FEN` tag if it has one.
+/// Comments and variations are skipped
+pub fn import_pgn(pgn: &str) -> Result<History, String> {
+    let mut variant = GameVariant::default();
+    let mut fen = None;
+    let mut movetext = String::new();
+    for line in pgn.lines() {
+        let line = line.trim();
+        if let Some(tag) = line.strip_prefix('[') {
+            // Tags after the moves belong to the next game
+            if !movetext.trim().is_empty() {
+                break;
+            }
+            if let Some(name) = tag.strip_prefix("Variant ") {
+                let name = name.trim_end_matches(']').trim().trim_matches('"');
+                variant = GameVariant::from_pgn_name(name)
+                    .ok_or_else(|| format!("unknown variant {} in PGN", name))?;
+            }
+            if let Some(tag_fen) = tag.strip_prefix("FEN ") {
+                let tag_fen = tag_fen.trim_end_matches(']').trim().trim_matches('"');
+                parse_fen(tag_fen)?;
+                fen = Some(tag_fen.to_string());
+            }
+            continue;
+        }
+        movetext.push_str(line);
+        movetext.push('\n');
+    }
+
+    let mut moves = String::new();
+    let mut comment = false;
+    let mut line_comment = false;
+    let mut variation_depth = 0;
+    for c in movetext.chars() {
+        match c {
+            '\n' => {
+                line_comment = false;
+                moves.push(' ');
+            }
+            _ if line_comment => {}
+            '{' => comment = true,
+            '}' => comment = false,
+            ';' if !comment => line_comment = true,
+            '(' if !comment => variation_depth += 1,
+            ')' if !comment => variation_depth -= 1,
+            _ if !comment && variation_depth == 0 => moves.push(c),
+            _ => {}
+        }
+    }
+
+    let mut history = History::new(fen, variant);
+    let start = history.start();
+    let mut pieces = start.pieces.clone();
+    let mut color = start.turn;
+    for token in moves.split_whitespace() {
+        // Results and annotation glyphs like `$1`
+        if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token) || token.starts_with('$') {
+            continue;
+        }
+        // Move numbers can be attached to the move, like `1.e4`, but castling can start with zeros
+        let token = match token.rfind('.') {
+            Some(end) => &token[end + 1..],
+            None if token.chars().all(|c| c.is_ascii_digit()) => "",
+            None => token,
+        };
+        if token.is_empty() {
+            continue;
+        }
+        let invalid = || format!("invalid move {} in PGN", token);
+        let MovePieceEvent {
+            from,
+            to,
+            promotion,
+        } = parse_san(&pieces, &history, color, token).ok_or_else(invalid)?;
+        let piece = history.piece_on(&pieces, from).ok_or_else(invalid)?;
+        let rules = history.rules();
+        let mv = rules
+            .find_move(&piece, &pieces, &history, to)
+            .ok_or_else(invalid)?;
+        let removed = rules.play(&mut pieces, from, mv, promotion);
+        let turn = Turn::new(&piece, to, &removed, &pieces, &history);
+        history.turns.push(turn);
+        color = color.opposite();
+    }
+    Ok(history)
+}
+
+/// Writes the PGN to a new file in the games directory, returning its path
+pub fn write_pgn(pgn: &str) -> io::Result<PathBuf> {
+    let dir = save_dir().join("games");
+    fs::create_dir_all(&dir)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let path = dir.join(format!("game-{}.pgn", timestamp));
+    fs::write(&path, pgn)?;
+    Ok(path)
+}
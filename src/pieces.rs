@@ -1,10 +1,16 @@
 use std::{iter::repeat};
 
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::history::History;
+use crate::{
+    config::Config,
+    headless::is_headless,
+    history::{Browse, History},
+    theme::{PieceMaterial, Themes},
+};
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum PieceColor {
     White,
     Black,
@@ -19,7 +25,7 @@ impl PieceColor {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum PieceType {
     King,
     Queen,
@@ -29,6 +35,24 @@ pub enum PieceType {
     Pawn,
 }
 
+pub const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::King,
+    PieceType::Queen,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Rook,
+    PieceType::Pawn,
+];
+
+/// Pieces a pawn can become on the last rank, the first being the one it gets unless another
+/// is asked for
+pub const PROMOTION_TYPES: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
 pub const KILL_ENERGY: u8 = 10;
 
 // impl PieceType {
@@ -44,7 +68,7 @@ pub const KILL_ENERGY: u8 = 10;
 //     }
 // }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Piece {
     pub color: PieceColor,
     pub piece_type: PieceType,
@@ -52,9 +76,42 @@ pub struct Piece {
     pub x: u8,
     pub y: u8,
     pub energy: u8,
+    /// Whether this king or rook hasn't moved yet, so it can still castle
+    #[serde(default)]
+    pub can_castle: bool,
+    /// Whether this piece was a pawn, which it goes back to when taken in drop variants
+    #[serde(default)]
+    pub promoted: bool,
+}
+
+pub const FIELD_SIZE: u8 = 8;
+
+/// Squares off the board stand for the pieces in hand in drop variants, so a drop is a move
+/// from there. White's are on this rank and Black's on the next, with the piece type as file
+pub const POCKET_RANK: u8 = FIELD_SIZE;
+
+pub fn pocket_square(color: PieceColor, piece_type: PieceType) -> (u8, u8) {
+    let rank = match color {
+        PieceColor::White => POCKET_RANK,
+        PieceColor::Black => POCKET_RANK + 1,
+    };
+    let file = PIECE_TYPES
+        .iter()
+        .position(|&other| other == piece_type)
+        .unwrap() as u8;
+    (rank, file)
 }
 
-const FIELD_SIZE: u8 = 8;
+/// Piece dropped by a move from `square`, if it's a pocket square
+pub fn dropped_piece(square: (u8, u8)) -> Option<(PieceColor, PieceType)> {
+    let color = match square.0 {
+        POCKET_RANK => PieceColor::White,
+        x if x == POCKET_RANK + 1 => PieceColor::Black,
+        _ => return None,
+    };
+    let piece_type = *PIECE_TYPES.get(square.1 as usize)?;
+    Some((color, piece_type))
+}
 
 pub type MovePosition = (u8, u8, Option<Takeable>);
 
@@ -122,14 +179,17 @@ fn valid_positions_for_pawn(
     } else {
         -1
     };
-    try_peace_move_pawn(poss, this, pieces, multiplier);
+    let can_push = try_peace_move_pawn(poss, this, pieces, multiplier);
     try_aggr_move_pawn(poss, this, pieces, multiplier);
-    if this.color == PieceColor::White {
-        if this.x == 1 {
-            try_peace_move_pawn(poss, this, pieces, 2);
-        }
-    } else if this.x == 6 {
-        try_peace_move_pawn(poss, this, pieces, -2);
+    // Pawns still on their first two ranks, like the horde's, can go two squares if the first
+    // one is free
+    let at_start = if this.color == PieceColor::White {
+        this.x <= 1
+    } else {
+        this.x >= 6
+    };
+    if can_push && at_start {
+        try_peace_move_pawn(poss, this, pieces, 2 * multiplier);
     }
     // En passant
     if let Some(last_turn) = last_turn {
@@ -161,16 +221,23 @@ fn valid_positions_for_pawn(
     }
 }
 
-fn try_peace_move_pawn(poss: &mut Vec<MovePosition>, this: &Piece, pieces: &[Piece], dx: i8) {
+/// Returns whether the pawn can move there
+fn try_peace_move_pawn(
+    poss: &mut Vec<MovePosition>,
+    this: &Piece,
+    pieces: &[Piece],
+    dx: i8,
+) -> bool {
     let x = if let Some(x) = check_add(this.x, dx as i8) {
         x
     } else {
-        return;
+        return false;
     };
     if pieces.iter().any(|piece| piece.x == x && piece.y == this.y) {
-        return;
+        return false;
     }
     poss.push((x, this.y, None));
+    true
 }
 
 fn try_aggr_move_pawn(poss: &mut Vec<MovePosition>, this: &Piece, pieces: &[Piece], dx: i8) {
@@ -237,6 +304,117 @@ fn valid_positions_for_king(
     }
 }
 
+/// Files the king and the rook end up on when castling with the rook on `rook_y`. They are the
+/// usual ones wherever the pieces started, as in Chess960
+pub fn castling_files(king_y: u8, rook_y: u8) -> (u8, u8) {
+    if rook_y > king_y {
+        (6, 5)
+    } else {
+        (2, 3)
+    }
+}
+
+/// Whether moving the piece on `from` to `to` is castling, which is written as the king moving
+/// to the square of the rook it castles with
+pub fn is_castling(pieces: &[Piece], from: (u8, u8), to: (u8, u8)) -> bool {
+    let piece_on = |square: (u8, u8)| pieces.iter().find(|piece| (piece.x, piece.y) == square);
+    match (piece_on(from), piece_on(to)) {
+        (Some(king), Some(rook)) => {
+            king.piece_type == PieceType::King
+                && rook.piece_type == PieceType::Rook
+                && king.color == rook.color
+        }
+        _ => false,
+    }
+}
+
+/// Whether `piece` moving to `to` is a pawn reaching the last rank, where it promotes
+pub fn promotes(piece: &Piece, to: (u8, u8)) -> bool {
+    let last_rank = match piece.color {
+        PieceColor::White => FIELD_SIZE - 1,
+        PieceColor::Black => 0,
+    };
+    piece.piece_type == PieceType::Pawn
+        && to.0 == last_rank
+        && dropped_piece((piece.x, piece.y)).is_none()
+}
+
+/// Whether a piece of `color` on `square` could be taken on the next move
+fn square_attacked(
+    pieces: &[Piece],
+    history: &History,
+    square: (u8, u8),
+    color: PieceColor,
+) -> bool {
+    let mut pieces: Vec<Piece> = pieces
+        .iter()
+        .filter(|piece| (piece.x, piece.y) != square)
+        .copied()
+        .collect();
+    pieces.push(Piece {
+        color,
+        piece_type: PieceType::King,
+        x: square.0,
+        y: square.1,
+        energy: 0,
+        can_castle: false,
+        promoted: false,
+    });
+    pieces
+        .iter()
+        .filter(|piece| piece.color != color)
+        .any(|piece| {
+            moves_without_castling(piece, &pieces, history).iter().any(
+                |&(_, _, takeable)| matches!(takeable, Some(Takeable(x, y)) if (x, y) == square),
+            )
+        })
+}
+
+fn valid_castlings(
+    poss: &mut Vec<MovePosition>,
+    this: &Piece,
+    pieces: &[Piece],
+    history: &History,
+) {
+    if !this.can_castle {
+        return;
+    }
+    for rook in pieces.iter().filter(|piece| {
+        piece.color == this.color
+            && piece.piece_type == PieceType::Rook
+            && piece.can_castle
+            && piece.x == this.x
+    }) {
+        let (king_to, rook_to) = castling_files(this.y, rook.y);
+        // Both pieces need every square they cross to be empty, but for the two of them
+        let files = [this.y, rook.y, king_to, rook_to];
+        let low = *files.iter().min().unwrap();
+        let high = *files.iter().max().unwrap();
+        if pieces.iter().any(|piece| {
+            piece.x == this.x
+                && (low..=high).contains(&piece.y)
+                && piece.y != this.y
+                && piece.y != rook.y
+        }) {
+            continue;
+        }
+        // The king can't castle out of, through or into an attack. The rook could be hiding one
+        let others: Vec<Piece> = pieces
+            .iter()
+            .filter(|piece| {
+                (piece.x, piece.y) != (this.x, this.y) && (piece.x, piece.y) != (rook.x, rook.y)
+            })
+            .copied()
+            .collect();
+        if (this.y.min(king_to)..=this.y.max(king_to))
+            .any(|y| square_attacked(&others, history, (this.x, y), this.color))
+        {
+            continue;
+        }
+        poss.push((this.x, rook.y, None));
+    }
+}
+
 fn try_move_in_line(
     poss: &mut Vec<MovePosition>,
     this: &Piece,
@@ -295,215 +473,361 @@ fn try_move(
 #[derive(Debug, Clone, Copy)]
 pub struct Takeable(pub u8, pub u8);
 
+// TODO: maybe SmallVec
+/// Moves of a piece, leaving out castling as it can't take anything
+fn moves_without_castling(this: &Piece, pieces: &[Piece], history: &History) -> Vec<MovePosition> {
+    let mut poss = Vec::new();
+    match this.piece_type {
+        PieceType::King => {
+            valid_positions_for_king(&mut poss, this, pieces);
+        }
+        PieceType::Rook => {
+            valid_positions_for_rook(&mut poss, this, pieces);
+        }
+        PieceType::Bishop => {
+            valid_positions_for_bishop(&mut poss, this, pieces);
+        }
+        PieceType::Queen => {
+            valid_positions_for_queen(&mut poss, this, pieces);
+        }
+        PieceType::Knight => {
+            valid_positions_for_knight(&mut poss, this, pieces);
+        }
+        PieceType::Pawn => valid_positions_for_pawn(&mut poss, this, pieces, history),
+    }
+    poss
+}
+
 impl Piece {
-    // TODO: maybe SmallVec
+    /// Piece held in hand, standing on its pocket square
+    pub fn in_pocket(color: PieceColor, piece_type: PieceType) -> Self {
+        let (x, y) = pocket_square(color, piece_type);
+        Piece {
+            color,
+            piece_type,
+            x,
+            y,
+            energy: 0,
+            can_castle: false,
+            promoted: false,
+        }
+    }
+
     /// History is only used for en passant
     pub fn valid_positions(
         &self,
         pieces: &[Piece],
         history: &History,
     ) -> Vec<(u8, u8, Option<Takeable>)> {
-        let mut poss = Vec::new();
-        match self.piece_type {
-            PieceType::King => {
-                valid_positions_for_king(&mut poss, self, pieces);
-            }
-            PieceType::Rook => {
-                valid_positions_for_rook(&mut poss, self, pieces);
-            }
-            PieceType::Bishop => {
-                valid_positions_for_bishop(&mut poss, self, pieces);
-            }
-            PieceType::Queen => {
-                valid_positions_for_queen(&mut poss, self, pieces);
-            }
-            PieceType::Knight => {
-                valid_positions_for_knight(&mut poss, self, pieces);
-            }
-            PieceType::Pawn => valid_positions_for_pawn(&mut poss, self, pieces, history),
+        let mut poss = moves_without_castling(self, pieces, history);
+        if self.piece_type == PieceType::King {
+            valid_castlings(&mut poss, self, pieces, history);
         }
         poss
     }
 }
 
-fn move_pieces(time: Res<Time>, mut query: Query<(&mut Transform, &Piece)>) {
+/// Squares of the pieces that could take the king of `color` on their next move
+pub fn king_attackers(pieces: &[Piece], history: &History, color: PieceColor) -> Vec<(u8, u8)> {
+    let king = if let Some(king) = pieces
+        .iter()
+        .find(|piece| piece.color == color && piece.piece_type == PieceType::King)
+    {
+        king
+    } else {
+        return Vec::new();
+    };
+    pieces
+        .iter()
+        .filter(|piece| piece.color != color)
+        .filter(|piece| {
+            piece
+                .valid_positions(pieces, history)
+                .iter()
+                .any(|&(_, _, takeable)| {
+                    matches!(takeable, Some(Takeable(x, y)) if (x, y) == (king.x, king.y))
+                })
+        })
+        .map(|piece| (piece.x, piece.y))
+        .collect()
+}
+
+/// Plays a move on a plain list of pieces, returning the taken piece if there is one. A move
+/// from a pocket square drops a new piece
+pub fn apply_move(pieces: &mut Vec<Piece>, from: (u8, u8), mv: MovePosition) -> Option<Piece> {
+    let (to_x, to_y, takeable) = mv;
+    if let Some((color, piece_type)) = dropped_piece(from) {
+        pieces.push(Piece {
+            x: to_x,
+            y: to_y,
+            ..Piece::in_pocket(color, piece_type)
+        });
+        return None;
+    }
+    if is_castling(pieces, from, (to_x, to_y)) {
+        let (king_y, rook_y) = castling_files(from.1, to_y);
+        for piece in pieces.iter_mut() {
+            if (piece.x, piece.y) == from {
+                piece.y = king_y;
+            } else if (piece.x, piece.y) == (to_x, to_y) {
+                piece.y = rook_y;
+            } else {
+                continue;
+            }
+            piece.can_castle = false;
+        }
+        return None;
+    }
+    let taken = takeable.and_then(|Takeable(x, y)| {
+        let index = pieces
+            .iter()
+            .position(|piece| piece.x == x && piece.y == y)?;
+        Some(pieces.remove(index))
+    });
+    if let Some(piece) = pieces
+        .iter_mut()
+        .find(|piece| piece.x == from.0 && piece.y == from.1)
+    {
+        piece.x = to_x;
+        piece.y = to_y;
+        piece.can_castle = false;
+    }
+    taken
+}
+
+/// Piece held with the mouse, which doesn't slide to its square until it's dropped
+pub struct Lifted;
+
+fn move_pieces(
+    time: Res<Time>,
+    config: Res<Config>,
+    mut query: Query<(&mut Transform, &Piece), Without<Lifted>>,
+) {
     for (mut transform, piece) in query.iter_mut() {
         // Get the direction to move in
         let direction = Vec3::new(piece.x as f32, 0., piece.y as f32) - transform.translation;
 
         // Only move if the piece isn't already there (distance is big)
         if direction.length() > 0.1 {
-            transform.translation += direction.normalize() * (time.delta_seconds() * 8.);
+            transform.translation +=
+                direction.normalize() * (time.delta_seconds() * config.animation_speed);
         }
     }
 }
 
-fn create_pieces(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
+/// Meshes and materials shared by every piece, so pieces can be spawned outside of startup
+pub struct PieceAssets {
+    king: Handle<Mesh>,
+    king_cross: Handle<Mesh>,
+    pawn: Handle<Mesh>,
+    knight_1: Handle<Mesh>,
+    knight_2: Handle<Mesh>,
+    rook: Handle<Mesh>,
+    bishop: Handle<Mesh>,
+    queen: Handle<Mesh>,
+    white_material: Handle<StandardMaterial>,
+    black_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for PieceAssets {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .unwrap();
+        let theme = world.get_resource::<Themes>().unwrap().current().clone();
+        let mut material = |piece_material: &PieceMaterial| {
+            let mut material = StandardMaterial::default();
+            piece_material.apply(&mut material);
+            materials.add(material)
+        };
+        PieceAssets {
+            // Load all the meshes
+            king: asset_server.load("models/chess_kit/pieces.glb#Mesh0/Primitive0"),
+            king_cross: asset_server.load("models/chess_kit/pieces.glb#Mesh1/Primitive0"),
+            pawn: asset_server.load("models/chess_kit/pieces.glb#Mesh2/Primitive0"),
+            knight_1: asset_server.load("models/chess_kit/pieces.glb#Mesh3/Primitive0"),
+            knight_2: asset_server.load("models/chess_kit/pieces.glb#Mesh4/Primitive0"),
+            rook: asset_server.load("models/chess_kit/pieces.glb#Mesh5/Primitive0"),
+            bishop: asset_server.load("models/chess_kit/pieces.glb#Mesh6/Primitive0"),
+            queen: asset_server.load("models/chess_kit/pieces.glb#Mesh7/Primitive0"),
+            // Add some materials
+            white_material: material(&theme.white_pieces),
+            black_material: material(&theme.black_pieces),
+        }
+    }
+}
+
+fn apply_piece_theme(
+    themes: Res<Themes>,
+    assets: Res<PieceAssets>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    // Load all the meshes
-    let king_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh0/Primitive0");
-    let king_cross_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh1/Primitive0");
-    let pawn_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh2/Primitive0");
-    let knight_1_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh3/Primitive0");
-    let knight_2_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh4/Primitive0");
-    let rook_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh5/Primitive0");
-    let bishop_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh6/Primitive0");
-    let queen_handle: Handle<Mesh> =
-        asset_server.load("models/chess_kit/pieces.glb#Mesh7/Primitive0");
-
-    // Add some materials
-    let white_material = materials.add(Color::rgb(1., 0.8, 0.8).into());
-    let black_material = materials.add(Color::rgb(0.3, 0.3, 0.3).into());
-
-    spawn_rook(
-        &mut commands,
-        white_material.clone(),
-        PieceColor::White,
-        rook_handle.clone(),
-        (0, 0),
-    );
-    spawn_knight(
-        &mut commands,
-        white_material.clone(),
-        PieceColor::White,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
+    if !themes.is_changed() {
+        return;
+    }
+    let theme = themes.current();
+    if let Some(material) = materials.get_mut(&assets.white_material) {
+        theme.white_pieces.apply(material);
+    }
+    if let Some(material) = materials.get_mut(&assets.black_material) {
+        theme.black_pieces.apply(material);
+    }
+}
+
+/// Spawns the entity for an arbitrary piece, keeping all of its state (e.g. energy).
+/// Without assets, when there is no window, the piece has no model
+pub fn spawn_piece(commands: &mut Commands, assets: Option<&PieceAssets>, piece: Piece) -> Entity {
+    let assets = match assets {
+        Some(assets) => assets,
+        None => return commands.spawn().insert(piece).id(),
+    };
+    let material = match piece.color {
+        PieceColor::White => assets.white_material.clone(),
+        PieceColor::Black => assets.black_material.clone(),
+    };
+    let position = (piece.x, piece.y);
+    let entity = match piece.piece_type {
+        PieceType::King => spawn_king(
+            commands,
+            material,
+            piece.color,
+            assets.king.clone(),
+            assets.king_cross.clone(),
+            position,
+        ),
+        PieceType::Queen => spawn_queen(
+            commands,
+            material,
+            piece.color,
+            assets.queen.clone(),
+            position,
+        ),
+        PieceType::Bishop => spawn_bishop(
+            commands,
+            material,
+            piece.color,
+            assets.bishop.clone(),
+            position,
+        ),
+        PieceType::Knight => spawn_knight(
+            commands,
+            material,
+            piece.color,
+            assets.knight_1.clone(),
+            assets.knight_2.clone(),
+            position,
+        ),
+        PieceType::Rook => spawn_rook(
+            commands,
+            material,
+            piece.color,
+            assets.rook.clone(),
+            position,
+        ),
+        PieceType::Pawn => spawn_pawn(
+            commands,
+            material,
+            piece.color,
+            assets.pawn.clone(),
+            position,
+        ),
+    };
+    // Overwrite the fresh piece with the full state
+    commands.entity(entity).insert(piece);
+    entity
+}
+
+/// Classic starting position
+pub fn starting_pieces() -> Vec<Piece> {
+    pieces_with_back_rank([
+        PieceType::Rook,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Queen,
+        PieceType::King,
+        PieceType::Bishop,
+        PieceType::Knight,
+        PieceType::Rook,
+    ])
+}
+
+/// Number of Chess960 starting positions
+pub const CHESS960_POSITIONS: u16 = 960;
+
+/// Chess960 starting position by its number, from 0 to 959. 518 is the classic one
+pub fn chess960_pieces(index: u16) -> Vec<Piece> {
+    let mut back_rank = [None; FIELD_SIZE as usize];
+    // Puts the piece on the `n`th empty square
+    let place = |back_rank: &mut [Option<PieceType>], n: usize, piece_type| {
+        let y = (0..back_rank.len())
+            .filter(|&y| back_rank[y].is_none())
+            .nth(n)
+            .expect("not enough empty squares");
+        back_rank[y] = Some(piece_type);
+    };
+    let n = (index % CHESS960_POSITIONS) as usize;
+    // One bishop on each color, then the queen and the knights on the empty squares
+    back_rank[n % 4 * 2 + 1] = Some(PieceType::Bishop);
+    let n = n / 4;
+    back_rank[n % 4 * 2] = Some(PieceType::Bishop);
+    let n = n / 4;
+    place(&mut back_rank, n % 6, PieceType::Queen);
+    let (knight_1, knight_2) = [
         (0, 1),
-    );
-    spawn_bishop(
-        &mut commands,
-        white_material.clone(),
-        PieceColor::White,
-        bishop_handle.clone(),
         (0, 2),
-    );
-    spawn_queen(
-        &mut commands,
-        white_material.clone(),
-        PieceColor::White,
-        queen_handle.clone(),
         (0, 3),
-    );
-    spawn_king(
-        &mut commands,
-        white_material.clone(),
-        PieceColor::White,
-        king_handle.clone(),
-        king_cross_handle.clone(),
         (0, 4),
-    );
-    spawn_bishop(
-        &mut commands,
-        white_material.clone(),
-        PieceColor::White,
-        bishop_handle.clone(),
-        (0, 5),
-    );
-    spawn_knight(
-        &mut commands,
-        white_material.clone(),
-        PieceColor::White,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
-        (0, 6),
-    );
-    spawn_rook(
-        &mut commands,
-        white_material.clone(),
-        PieceColor::White,
-        rook_handle.clone(),
-        (0, 7),
-    );
-
-    for i in 0..8 {
-        spawn_pawn(
-            &mut commands,
-            white_material.clone(),
-            PieceColor::White,
-            pawn_handle.clone(),
-            (1, i),
-        );
-    }
-
-    spawn_rook(
-        &mut commands,
-        black_material.clone(),
-        PieceColor::Black,
-        rook_handle.clone(),
-        (7, 0),
-    );
-    spawn_knight(
-        &mut commands,
-        black_material.clone(),
-        PieceColor::Black,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
-        (7, 1),
-    );
-    spawn_bishop(
-        &mut commands,
-        black_material.clone(),
-        PieceColor::Black,
-        bishop_handle.clone(),
-        (7, 2),
-    );
-    spawn_queen(
-        &mut commands,
-        black_material.clone(),
-        PieceColor::Black,
-        queen_handle,
-        (7, 3),
-    );
-    spawn_king(
-        &mut commands,
-        black_material.clone(),
-        PieceColor::Black,
-        king_handle,
-        king_cross_handle,
-        (7, 4),
-    );
-    spawn_bishop(
-        &mut commands,
-        black_material.clone(),
-        PieceColor::Black,
-        bishop_handle,
-        (7, 5),
-    );
-    spawn_knight(
-        &mut commands,
-        black_material.clone(),
-        PieceColor::Black,
-        knight_1_handle,
-        knight_2_handle,
-        (7, 6),
-    );
-    spawn_rook(
-        &mut commands,
-        black_material.clone(),
-        PieceColor::Black,
-        rook_handle,
-        (7, 7),
-    );
-
-    for i in 0..8 {
-        spawn_pawn(
-            &mut commands,
-            black_material.clone(),
-            PieceColor::Black,
-            pawn_handle.clone(),
-            (6, i),
-        );
+        (1, 2),
+        (1, 3),
+        (1, 4),
+        (2, 3),
+        (2, 4),
+        (3, 4),
+    ][n / 6];
+    // The second knight is placed once the first one has taken a square
+    place(&mut back_rank, knight_1, PieceType::Knight);
+    place(&mut back_rank, knight_2 - 1, PieceType::Knight);
+    // The king goes between the rooks
+    place(&mut back_rank, 0, PieceType::Rook);
+    place(&mut back_rank, 0, PieceType::King);
+    place(&mut back_rank, 0, PieceType::Rook);
+    let mut pieces = [PieceType::Pawn; FIELD_SIZE as usize];
+    for (piece, placed) in pieces.iter_mut().zip(back_rank.iter()) {
+        *piece = placed.expect("every square is filled");
+    }
+    pieces_with_back_rank(pieces)
+}
+
+/// Pieces of both sides with the pawns on their usual rank, able to castle
+fn pieces_with_back_rank(back_rank: [PieceType; FIELD_SIZE as usize]) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    for &(color, back_x, pawn_x) in [(PieceColor::White, 0, 1), (PieceColor::Black, 7, 6)].iter() {
+        for (y, &piece_type) in back_rank.iter().enumerate() {
+            pieces.push(Piece {
+                color,
+                piece_type,
+                x: back_x,
+                y: y as u8,
+                energy: 0,
+                can_castle: matches!(piece_type, PieceType::King | PieceType::Rook),
+                promoted: false,
+            });
+        }
+        for y in 0..FIELD_SIZE {
+            pieces.push(Piece {
+                color,
+                piece_type: PieceType::Pawn,
+                x: pawn_x,
+                y,
+                energy: 0,
+                can_castle: false,
+                promoted: false,
+            });
+        }
     }
+    pieces
 }
 
 fn spawn_king(
@@ -513,7 +837,7 @@ fn spawn_king(
     mesh: Handle<Mesh>,
     mesh_cross: Handle<Mesh>,
     position: (u8, u8),
-) {
+) -> Entity {
     commands
         // Spawn parent entity
         .spawn_bundle(PbrBundle {
@@ -530,6 +854,8 @@ fn spawn_king(
             x: position.0,
             y: position.1,
             energy: 0,
+            can_castle: false,
+            promoted: false,
         })
         // Add children to the parent
         .with_children(|parent| {
@@ -553,7 +879,8 @@ fn spawn_king(
                 },
                 ..Default::default()
             });
-        });
+        })
+        .id()
 }
 
 fn spawn_knight(
@@ -563,7 +890,7 @@ fn spawn_knight(
     mesh_1: Handle<Mesh>,
     mesh_2: Handle<Mesh>,
     position: (u8, u8),
-) {
+) -> Entity {
     commands
         // Spawn parent entity
         .spawn_bundle(PbrBundle {
@@ -580,6 +907,8 @@ fn spawn_knight(
             x: position.0,
             y: position.1,
             energy: 0,
+            can_castle: false,
+            promoted: false,
         })
         // Add children to the parent
         .with_children(|parent| {
@@ -603,7 +932,8 @@ fn spawn_knight(
                 },
                 ..Default::default()
             });
-        });
+        })
+        .id()
 }
 
 fn spawn_queen(
@@ -612,7 +942,7 @@ fn spawn_queen(
     piece_color: PieceColor,
     mesh: Handle<Mesh>,
     position: (u8, u8),
-) {
+) -> Entity {
     commands
         // Spawn parent entity
         .spawn_bundle(PbrBundle {
@@ -629,6 +959,8 @@ fn spawn_queen(
             x: position.0,
             y: position.1,
             energy: 0,
+            can_castle: false,
+            promoted: false,
         })
         .with_children(|parent| {
             parent.spawn_bundle(PbrBundle {
@@ -641,7 +973,8 @@ fn spawn_queen(
                 },
                 ..Default::default()
             });
-        });
+        })
+        .id()
 }
 
 fn spawn_bishop(
@@ -650,7 +983,7 @@ fn spawn_bishop(
     piece_color: PieceColor,
     mesh: Handle<Mesh>,
     position: (u8, u8),
-) {
+) -> Entity {
     commands
         // Spawn parent entity
         .spawn_bundle(PbrBundle {
@@ -667,6 +1000,8 @@ fn spawn_bishop(
             x: position.0,
             y: position.1,
             energy: 0,
+            can_castle: false,
+            promoted: false,
         })
         .with_children(|parent| {
             parent.spawn_bundle(PbrBundle {
@@ -679,7 +1014,8 @@ fn spawn_bishop(
                 },
                 ..Default::default()
             });
-        });
+        })
+        .id()
 }
 
 fn spawn_rook(
@@ -688,7 +1024,7 @@ fn spawn_rook(
     piece_color: PieceColor,
     mesh: Handle<Mesh>,
     position: (u8, u8),
-) {
+) -> Entity {
     commands
         // Spawn parent entity
         .spawn_bundle(PbrBundle {
@@ -705,6 +1041,8 @@ fn spawn_rook(
             x: position.0,
             y: position.1,
             energy: 0,
+            can_castle: false,
+            promoted: false,
         })
         .with_children(|parent| {
             parent.spawn_bundle(PbrBundle {
@@ -717,7 +1055,8 @@ fn spawn_rook(
                 },
                 ..Default::default()
             });
-        });
+        })
+        .id()
 }
 
 fn spawn_pawn(
@@ -726,7 +1065,7 @@ fn spawn_pawn(
     piece_color: PieceColor,
     mesh: Handle<Mesh>,
     position: (u8, u8),
-) {
+) -> Entity {
     commands
         // Spawn parent entity
         .spawn_bundle(PbrBundle {
@@ -743,6 +1082,8 @@ fn spawn_pawn(
             x: position.0,
             y: position.1,
             energy: 0,
+            can_castle: false,
+            promoted: false,
         })
         .with_children(|parent| {
             parent.spawn_bundle(PbrBundle {
@@ -755,13 +1096,69 @@ fn spawn_pawn(
                 },
                 ..Default::default()
             });
-        });
+        })
+        .id()
+}
+
+/// Piece shown instead of the live ones while browsing the history
+struct BrowsePiece;
+
+/// Shows the position of the browsed turn, hiding the pieces of the game meanwhile
+fn show_browsed_position(
+    mut commands: Commands,
+    assets: Res<PieceAssets>,
+    history: Res<History>,
+    mut browse: ResMut<Browse>,
+    browse_pieces_query: Query<Entity, With<BrowsePiece>>,
+    pieces_query: Query<&Children, With<Piece>>,
+    mut visible_query: Query<&mut Visible>,
+) {
+    let changed = history.is_changed() || browse.is_changed();
+    // A new or loaded game may not have the browsed turn
+    if changed && browse.0.map_or(false, |index| index >= history.turns.len()) {
+        browse.0 = None;
+    }
+    // Checked on every frame while browsing, as pieces spawned meanwhile by a promotion or a drop
+    // start out visible
+    if changed || browse.0.is_some() {
+        let is_visible = browse.0.is_none();
+        for children in pieces_query.iter() {
+            for child in children.iter() {
+                if let Ok(mut visible) = visible_query.get_mut(*child) {
+                    if visible.is_visible != is_visible {
+                        visible.is_visible = is_visible;
+                    }
+                }
+            }
+        }
+    }
+    if !changed {
+        return;
+    }
+    for entity in browse_pieces_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Some(index) = browse.0 {
+        for piece in history.position_after(index + 1) {
+            let entity = spawn_piece(&mut commands, Some(&assets), piece);
+            commands
+                .entity(entity)
+                .remove::<Piece>()
+                .insert(BrowsePiece);
+        }
+    }
 }
 
 pub struct PiecesPlugin;
 impl Plugin for PiecesPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(create_pieces.system())
-            .add_system(move_pieces.system());
+        // Pieces are only their state without a window
+        if is_headless(app) {
+            return;
+        }
+        app.init_resource::<PieceAssets>()
+            .add_system(move_pieces.system())
+            .add_system(apply_piece_theme.system())
+            .add_system(show_browsed_position.system());
     }
 }
@@ -0,0 +1,270 @@
+use std::{fmt::Display, fs, io, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{PlayerTurn, Premove, ResetSelectedEvent, Taken},
+    cli::PendingLaunch,
+    clock::Clocks,
+    headless::is_headless,
+    history::{History, Turn},
+    menu::{AppState, GameSetup},
+    pieces::*,
+};
+
+/// Bump this whenever a change to `SaveGame` makes older files unreadable
+const SAVE_VERSION: u32 = 1;
+
+/// Full snapshot of a game, enough to restore it exactly
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub turn: PieceColor,
+    pub pieces: Vec<Piece>,
+    pub history: History,
+    #[serde(default)]
+    pub clocks: Clocks,
+    #[serde(default)]
+    pub setup: GameSetup,
+}
+
+impl SaveGame {
+    /// Game at the end of the history, like one read from a PGN. The variant and the starting
+    /// position of `setup` are replaced by the ones of the history
+    pub fn from_history(history: History, mut setup: GameSetup) -> Self {
+        setup.variant = history.variant;
+        setup.fen = history.fen().map(str::to_string);
+        setup.chess960_position = None;
+        let start = history.start();
+        let turn = if history.turns.len() % 2 == 0 {
+            start.turn
+        } else {
+            start.turn.opposite()
+        };
+        SaveGame {
+            version: SAVE_VERSION,
+            turn,
+            pieces: history.position_after(history.turns.len()),
+            clocks: Clocks::new(setup.time_control),
+            history,
+            setup,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Format(ron::Error),
+    Version(u32),
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "couldn't access save file: {}", err),
+            SaveError::Format(err) => write!(f, "save file is malformed: {}", err),
+            SaveError::Version(version) => write!(
+                f,
+                "save file has version {}, but only version {} is supported",
+                version, SAVE_VERSION
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<ron::Error> for SaveError {
+    fn from(err: ron::Error) -> Self {
+        SaveError::Format(err)
+    }
+}
+
+/// Directory where all the saves live, falling back to the working directory
+pub fn save_dir() -> PathBuf {
+    dirs::data_dir()
+        .map(|dir| dir.join("bevy_chess"))
+        .unwrap_or_else(|| PathBuf::from("saves"))
+}
+
+pub fn autosave_path() -> PathBuf {
+    save_dir().join("autosave.ron")
+}
+
+pub fn quicksave_path() -> PathBuf {
+    save_dir().join("quicksave.ron")
+}
+
+pub fn write_save(path: &PathBuf, save: &SaveGame) -> Result<(), SaveError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let contents = ron::ser::to_string_pretty(save, Default::default())?;
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+pub fn read_save(path: &PathBuf) -> Result<SaveGame, SaveError> {
+    let contents = fs::read_to_string(path)?;
+    let save: SaveGame = ron::from_str(&contents)?;
+    if save.version != SAVE_VERSION {
+        return Err(SaveError::Version(save.version));
+    }
+    Ok(save)
+}
+
+fn snapshot<'a>(
+    turn: &PlayerTurn,
+    history: &History,
+    clocks: &Clocks,
+    setup: &GameSetup,
+    pieces: impl Iterator<Item = &'a Piece>,
+) -> SaveGame {
+    SaveGame {
+        version: SAVE_VERSION,
+        turn: turn.0,
+        pieces: pieces.copied().collect(),
+        history: history.clone(),
+        clocks: *clocks,
+        setup: setup.clone(),
+    }
+}
+
+/// Game found in the autosave at startup, that can be continued
+#[derive(Default)]
+pub struct LastGame(pub Option<SaveGame>);
+
+/// Replaces the current game with the one in the save
+pub struct LoadGameEvent(pub SaveGame);
+
+/// Asks whether to continue the game in the autosave, unless a game was given on the command line
+fn find_last_game(
+    mut last_game: ResMut<LastGame>,
+    launch: Res<PendingLaunch>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let path = autosave_path();
+    if !path.exists() {
+        return;
+    }
+    match read_save(&path) {
+        Ok(save) => last_game.0 = Some(save),
+        Err(err) => {
+            println!("Can't continue last game, {}", err);
+            return;
+        }
+    }
+    if launch.0.is_none() {
+        state.set(AppState::ContinuePrompt).unwrap();
+    }
+}
+
+/// A finished game can't be continued
+fn clear_autosave() {
+    let path = autosave_path();
+    if path.exists() {
+        if let Err(err) = fs::remove_file(&path) {
+            println!("Couldn't remove autosave, {}", err);
+        }
+    }
+}
+
+fn autosave(
+    mut turn_event_r: EventReader<Turn>,
+    turn: Res<PlayerTurn>,
+    history: Res<History>,
+    clocks: Res<Clocks>,
+    setup: Res<GameSetup>,
+    pieces_query: Query<&Piece, Without<Taken>>,
+) {
+    if turn_event_r.iter().next().is_none() {
+        return;
+    }
+    let save = snapshot(&turn, &history, &clocks, &setup, pieces_query.iter());
+    if let Err(err) = write_save(&autosave_path(), &save) {
+        println!("Autosave failed, {}", err);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn quicksave(
+    keyboard_input: Res<Input<KeyCode>>,
+    turn: Res<PlayerTurn>,
+    history: Res<History>,
+    clocks: Res<Clocks>,
+    setup: Res<GameSetup>,
+    pieces_query: Query<&Piece, Without<Taken>>,
+    mut load_game_w: EventWriter<LoadGameEvent>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        let save = snapshot(&turn, &history, &clocks, &setup, pieces_query.iter());
+        match write_save(&quicksave_path(), &save) {
+            Ok(()) => println!("Game saved"),
+            Err(err) => println!("Saving failed, {}", err),
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::F9) {
+        match read_save(&quicksave_path()) {
+            Ok(save) => load_game_w.send(LoadGameEvent(save)),
+            Err(err) => println!("Loading failed, {}", err),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn load_game(
+    mut commands: Commands,
+    mut load_game_r: EventReader<LoadGameEvent>,
+    piece_assets: Option<Res<PieceAssets>>,
+    pieces_query: Query<Entity, With<Piece>>,
+    mut turn: ResMut<PlayerTurn>,
+    mut history: ResMut<History>,
+    mut clocks: ResMut<Clocks>,
+    mut setup: ResMut<GameSetup>,
+    mut premove: ResMut<Premove>,
+    mut reset_selected_event: EventWriter<ResetSelectedEvent>,
+) {
+    let save = if let Some(LoadGameEvent(save)) = load_game_r.iter().last() {
+        save
+    } else {
+        return;
+    };
+    for entity in pieces_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for &piece in save.pieces.iter() {
+        spawn_piece(&mut commands, piece_assets.as_deref(), piece);
+    }
+    turn.0 = save.turn;
+    *history = save.history.clone();
+    *clocks = save.clocks;
+    *setup = save.setup.clone();
+    premove.0 = None;
+    reset_selected_event.send(ResetSelectedEvent);
+}
+
+pub struct SavePlugin;
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<LastGame>()
+            .add_event::<LoadGameEvent>()
+            .add_system(load_game.system().before("select_square"));
+        // Games played without a window are scripted, and shouldn't replace the player's game
+        if is_headless(app) {
+            return;
+        }
+        app.add_startup_system(find_last_game.system().before("launch_game"))
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(quicksave.system()))
+            .add_system_set(
+                SystemSet::on_enter(AppState::GameOver).with_system(clear_autosave.system()),
+            )
+            // Taken pieces are only marked by the end of Update, and the turn is recorded by then
+            .add_system_to_stage(CoreStage::PostUpdate, autosave.system());
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// Look of a piece color
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PieceMaterial {
+    pub color: Color,
+    pub metallic: f32,
+    pub roughness: f32,
+}
+
+impl PieceMaterial {
+    pub fn apply(&self, material: &mut StandardMaterial) {
+        material.base_color = self.color;
+        material.metallic = self.metallic;
+        material.roughness = self.roughness;
+    }
+}
+
+/// Colors and font of the board, the pieces and the UI, loaded from `assets/themes`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub name: String,
+    pub white_square: Color,
+    pub black_square: Color,
+    pub highlight: Color,
+    pub selected: Color,
+    pub movable_white: Color,
+    pub movable_black: Color,
+    pub last_move_white: Color,
+    pub last_move_black: Color,
+    pub check: Color,
+    pub attacker: Color,
+    pub premove: Color,
+    /// Squares the kings race to in some variants
+    pub goal_white: Color,
+    pub goal_black: Color,
+    /// Squares hidden in fog of war games
+    pub fog: Color,
+    pub white_pieces: PieceMaterial,
+    pub black_pieces: PieceMaterial,
+    pub background: Color,
+    /// Font of all the texts, relative to the assets directory
+    pub font: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: "Classic".to_string(),
+            white_square: Color::rgb(1., 0.9, 0.9),
+            black_square: Color::rgb(0., 0.1, 0.1),
+            highlight: Color::rgb(0.8, 0.3, 0.3),
+            selected: Color::rgb(0.9, 0.1, 0.1),
+            movable_white: Color::rgb(0.7, 0.9, 0.9),
+            movable_black: Color::rgb(0., 0.3, 0.3),
+            last_move_white: Color::rgb(0.9, 0.9, 0.6),
+            last_move_black: Color::rgb(0.3, 0.3, 0.1),
+            check: Color::rgb(0.9, 0.1, 0.1),
+            attacker: Color::rgb(0.9, 0.5, 0.2),
+            premove: Color::rgb(0.5, 0.3, 0.7),
+            goal_white: Color::rgb(0.7, 0.9, 0.6),
+            goal_black: Color::rgb(0.2, 0.4, 0.1),
+            fog: Color::rgb(0.15, 0.15, 0.18),
+            white_pieces: PieceMaterial {
+                color: Color::rgb(1., 0.8, 0.8),
+                metallic: 0.01,
+                roughness: 0.089,
+            },
+            black_pieces: PieceMaterial {
+                color: Color::rgb(0.3, 0.3, 0.3),
+                metallic: 0.01,
+                roughness: 0.089,
+            },
+            background: ClearColor::default().0,
+            font: "fonts/FiraSans-Bold.ttf".to_string(),
+        }
+    }
+}
+
+/// Every theme found, and the one in use
+pub struct Themes {
+    pub list: Vec<Theme>,
+    pub current: usize,
+}
+
+impl Themes {
+    pub fn current(&self) -> &Theme {
+        &self.list[self.current]
+    }
+
+    pub fn next(&mut self) {
+        self.current = (self.current + 1) % self.list.len();
+    }
+
+    pub fn select(&mut self, name: &str) {
+        match self.list.iter().position(|theme| theme.name == name) {
+            Some(index) => self.current = index,
+            None => println!("No theme named {}", name),
+        }
+    }
+}
+
+/// Every theme in `assets/themes`, starting with the one in the config
+impl FromWorld for Themes {
+    fn from_world(world: &mut World) -> Self {
+        let mut list = vec![Theme::default()];
+        list.extend(load_themes(&assets_dir().join("themes")));
+        let mut themes = Themes { list, current: 0 };
+        if let Some(config) = world.get_resource::<Config>() {
+            themes.select(&config.theme);
+        }
+        themes
+    }
+}
+
+/// Directory bevy loads the assets from
+fn assets_dir() -> PathBuf {
+    std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .or_else(|| Some(std::env::current_exe().ok()?.parent()?.to_path_buf()))
+        .unwrap_or_default()
+        .join("assets")
+}
+
+/// Reads every `.ron` theme in `dir`, sorted by name
+fn load_themes(dir: &Path) -> Vec<Theme> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            println!("Couldn't read themes from {}, {}", dir.display(), err);
+            return Vec::new();
+        }
+    };
+    let mut themes: Vec<Theme> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "ron"))
+        .filter_map(|path| {
+            let theme = fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|contents| ron::from_str(&contents).map_err(|err| err.to_string()));
+            match theme {
+                Ok(theme) => Some(theme),
+                Err(err) => {
+                    println!("Skipping theme {}, {}", path.display(), err);
+                    None
+                }
+            }
+        })
+        .collect();
+    themes.sort_by(|a: &Theme, b: &Theme| a.name.cmp(&b.name));
+    themes
+}
+
+/// Font of the current theme
+pub struct ThemeFont(pub Handle<Font>);
+
+impl FromWorld for ThemeFont {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let themes = world.get_resource::<Themes>().unwrap();
+        ThemeFont(asset_server.load(themes.current().font.as_str()))
+    }
+}
+
+fn apply_background(themes: Res<Themes>, mut clear_color: ResMut<ClearColor>) {
+    if themes.is_changed() {
+        clear_color.0 = themes.current().background;
+    }
+}
+
+/// Sets the font of the theme on every text, including the ones spawned later
+fn apply_font(
+    themes: Res<Themes>,
+    asset_server: Res<AssetServer>,
+    mut font: ResMut<ThemeFont>,
+    mut queries: QuerySet<(Query<&mut Text>, Query<&mut Text, Added<Text>>)>,
+) {
+    if themes.is_changed() {
+        font.0 = asset_server.load(themes.current().font.as_str());
+        for mut text in queries.q0_mut().iter_mut() {
+            for section in text.sections.iter_mut() {
+                section.style.font = font.0.clone();
+            }
+        }
+    }
+    for mut text in queries.q1_mut().iter_mut() {
+        for section in text.sections.iter_mut() {
+            if section.style.font != font.0 {
+                section.style.font = font.0.clone();
+            }
+        }
+    }
+}
+
+pub struct ThemePlugin;
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Themes>()
+            .init_resource::<ThemeFont>()
+            .init_resource::<ClearColor>()
+            .add_system(apply_background.system())
+            .add_system(apply_font.system());
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        mpsc::{channel, Receiver, RecvTimeoutError},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+/// How long an engine has to answer `uci` and `isready`
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// External engine speaking the UCI protocol, running as a child process
+pub struct Engine {
+    process: Child,
+    stdin: ChildStdin,
+    output: Mutex<Receiver<String>>,
+}
+
+impl Engine {
+    pub fn spawn(path: &str) -> io::Result<Self> {
+        let mut process = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = process.stdin.take().expect("stdin is piped");
+        let stdout = process.stdout.take().expect("stdout is piped");
+
+        // Read the engine output on its own thread, so the game never blocks on it
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Engine {
+            process,
+            stdin,
+            output: Mutex::new(receiver),
+        };
+        engine.send("uci")?;
+        engine.wait_for("uciok")?;
+        // Castling is sent as the king taking its own rook, which is how engines write it in
+        // Chess960 mode. The classic game is just another Chess960 position
+        engine.send("setoption name UCI_Chess960 value true")?;
+        engine.send("ucinewgame")?;
+        engine.send("isready")?;
+        engine.wait_for("readyok")?;
+        Ok(engine)
+    }
+
+    /// Blocks until the engine answers with `answer`, skipping what it says before
+    fn wait_for(&self, answer: &str) -> io::Result<()> {
+        let output = self.output.lock().unwrap();
+        loop {
+            match output.recv_timeout(HANDSHAKE_TIMEOUT) {
+                Ok(line) if line.trim() == answer => return Ok(()),
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("no {} from the engine", answer),
+                    ))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("the engine quit before {}", answer),
+                    ))
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()
+    }
+
+    /// Asks for the best move after `moves` (in UCI coordinates) from the starting position, or
+    /// from `fen` if given
+    pub fn go(
+        &mut self,
+        fen: Option<&str>,
+        moves: &[String],
+        movetime: Duration,
+    ) -> io::Result<()> {
+        let position = match fen {
+            Some(fen) => format!("position fen {}", fen),
+            None => "position startpos".to_string(),
+        };
+        if moves.is_empty() {
+            self.send(&position)?;
+        } else {
+            self.send(&format!("{} moves {}", position, moves.join(" ")))?;
+        }
+        self.send(&format!("go movetime {}", movetime.as_millis()))
+    }
+
+    /// Returns the answer to the last `go`, once the engine has one
+    pub fn best_move(&self) -> Option<String> {
+        let output = self.output.lock().unwrap();
+        while let Ok(line) = output.try_recv() {
+            if let Some(best_move) = line.strip_prefix("bestmove ") {
+                return best_move.split_whitespace().next().map(str::to_string);
+            }
+        }
+        None
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.process.kill();
+    }
+}
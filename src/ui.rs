@@ -1,10 +1,76 @@
-use crate::{board::*, history::History, pieces::*};
-use bevy::prelude::*;
+use crate::{
+    board::*,
+    captured::Captured,
+    clock::{format_clock, Clocks},
+    config::Config,
+    fog::Fog,
+    history::{Browse, History},
+    menu::GameSetup,
+    notation::{piece_letter, san_moves},
+    pieces::*,
+};
+use bevy::{input::mouse::MouseWheel, prelude::*};
 
 // Component to mark the Text entity
 struct NextMoveText;
 
-struct LastTurnText;
+/// Node holding the rows of the move list
+struct MoveList;
+
+struct MoveListRow;
+
+/// Button of the move with this index in the history
+struct MoveButton(usize);
+
+struct BackToGameButton;
+
+/// Width of the move list panel on the right
+pub const MOVE_LIST_WIDTH: f32 = 260.;
+/// Rows of the move list shown at once
+const MOVE_LIST_ROWS: usize = 20;
+
+/// First row shown in the move list, or `None` to follow the latest move
+#[derive(Default)]
+struct MoveListScroll(Option<usize>);
+
+struct MoveListMaterials {
+    none: Handle<ColorMaterial>,
+    hovered: Handle<ColorMaterial>,
+    current: Handle<ColorMaterial>,
+}
+
+impl FromWorld for MoveListMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        MoveListMaterials {
+            none: materials.add(Color::NONE.into()),
+            hovered: materials.add(Color::rgba(0.8, 0.8, 0.8, 0.2).into()),
+            current: materials.add(Color::rgba(0.8, 0.3, 0.3, 0.6).into()),
+        }
+    }
+}
+
+struct ClockText;
+
+struct MaterialText;
+
+struct ChecksText;
+
+/// Node holding the pieces in hand, in drop variants
+struct Pockets;
+
+struct PocketRow;
+
+/// Button of a type of piece in hand
+struct PocketButton(PieceColor, PieceType);
+
+/// Node holding the pieces a pawn can promote to, while the player picks one
+struct PromotionPicker;
+
+struct PromotionRow;
+
+/// Button of a piece the pawn can promote to
+struct PromotionButton(PieceType);
 
 /// Initialize UiCamera and text
 fn init_next_move_text(
@@ -47,8 +113,108 @@ fn init_next_move_text(
                     ..Default::default()
                 })
                 .insert(NextMoveText);
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: Rect {
+                            top: Val::Px(50.),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 20.0,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(ClockText);
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: Rect {
+                            top: Val::Px(75.),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 20.0,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(MaterialText);
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: Rect {
+                            top: Val::Px(100.),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 20.0,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(ChecksText);
         });
 
+    ui_cmds
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: material.clone(),
+            ..Default::default()
+        })
+        .insert(Pockets);
+
+    ui_cmds
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    top: Val::Px(130.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::Row,
+                ..Default::default()
+            },
+            material: material.clone(),
+            ..Default::default()
+        })
+        .insert(PromotionPicker);
+
     ui_cmds
         .spawn_bundle(NodeBundle {
             style: Style {
@@ -58,9 +224,11 @@ fn init_next_move_text(
                     top: Val::Px(10.),
                     ..Default::default()
                 },
+                size: Size::new(Val::Px(MOVE_LIST_WIDTH), Val::Auto),
+                flex_direction: FlexDirection::ColumnReverse,
                 ..Default::default()
             },
-            material,
+            material: material.clone(),
             ..Default::default()
         })
         .with_children(|parent| {
@@ -77,19 +245,46 @@ fn init_next_move_text(
                 ..Default::default()
             });
             parent
-                .spawn_bundle(TextBundle {
-                    text: Text::with_section(
-                        "Last turn",
-                        TextStyle {
-                            font: font.clone(),
-                            font_size: 20.0,
-                            color: Color::rgb(0.8, 0.8, 0.8),
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        ..Default::default()
+                    },
+                    material: material.clone(),
+                    ..Default::default()
+                })
+                .insert(MoveList);
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        margin: Rect {
+                            top: Val::Px(10.),
+                            ..Default::default()
                         },
-                        Default::default(),
-                    ),
+                        ..Default::default()
+                    },
+                    material: material.clone(),
+                    visible: Visible {
+                        is_visible: false,
+                        is_transparent: true,
+                    },
                     ..Default::default()
                 })
-                .insert(LastTurnText);
+                .insert(BackToGameButton)
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            "Back to game (Esc)",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 20.0,
+                                color: Color::rgb(0.8, 0.3, 0.3),
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    });
+                });
             // let material = color_materials.add(asset_server.load("combust_particle.png").into());
             // parent.spawn_bundle(ImageBundle {
             //     material,
@@ -98,6 +293,236 @@ fn init_next_move_text(
         });
 }
 
+/// Rebuild the rows of pieces in hand, one button per type with how many there are
+#[allow(clippy::too_many_arguments)]
+fn pockets_update(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    materials: Res<MoveListMaterials>,
+    history: Res<History>,
+    turn: Res<PlayerTurn>,
+    selected_drop: Res<SelectedDrop>,
+    pockets_query: Query<Entity, With<Pockets>>,
+    rows_query: Query<Entity, With<PocketRow>>,
+) {
+    if !history.is_changed() && !selected_drop.is_changed() {
+        return;
+    }
+    for entity in rows_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let pockets = match pockets_query.iter().next() {
+        Some(pockets) if history.rules().has_drops() => pockets,
+        _ => return,
+    };
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 20.0,
+        color: Color::rgb(0.8, 0.8, 0.8),
+    };
+
+    commands.entity(pockets).with_children(|parent| {
+        for &(color, name) in [(PieceColor::White, "White"), (PieceColor::Black, "Black")].iter() {
+            let pocket = history.pocket(color);
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        ..Default::default()
+                    },
+                    material: materials.none.clone(),
+                    ..Default::default()
+                })
+                .insert(PocketRow)
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(60.), Val::Px(24.)),
+                            ..Default::default()
+                        },
+                        text: Text::with_section(name, text_style.clone(), Default::default()),
+                        ..Default::default()
+                    });
+                    for &piece_type in PIECE_TYPES.iter() {
+                        let count = pocket.iter().filter(|&&held| held == piece_type).count();
+                        if count == 0 {
+                            continue;
+                        }
+                        let selected = color == turn.0 && selected_drop.0 == Some(piece_type);
+                        let label = format!("{}{}", piece_letter(piece_type).unwrap_or('P'), count);
+                        parent
+                            .spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(40.), Val::Px(24.)),
+                                    padding: Rect {
+                                        left: Val::Px(5.),
+                                        ..Default::default()
+                                    },
+                                    ..Default::default()
+                                },
+                                material: if selected {
+                                    materials.current.clone()
+                                } else {
+                                    materials.none.clone()
+                                },
+                                ..Default::default()
+                            })
+                            .insert(PocketButton(color, piece_type))
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle {
+                                    text: Text::with_section(
+                                        label,
+                                        text_style.clone(),
+                                        Default::default(),
+                                    ),
+                                    ..Default::default()
+                                });
+                            });
+                    }
+                });
+        }
+    });
+}
+
+/// Clicking a piece in hand of the side to move picks it to be dropped
+fn pocket_buttons(
+    setup: Res<GameSetup>,
+    turn: Res<PlayerTurn>,
+    browse: Res<Browse>,
+    mut selected_piece: ResMut<Option<SelectedPiece>>,
+    mut selected_drop: ResMut<SelectedDrop>,
+    query: Query<(&Interaction, &PocketButton), Changed<Interaction>>,
+) {
+    for (interaction, &PocketButton(color, piece_type)) in query.iter() {
+        if *interaction == Interaction::Clicked
+            && color == turn.0
+            && setup.is_local(color)
+            && browse.0.is_none()
+        {
+            selected_piece.take();
+            selected_drop.0 = Some(piece_type);
+        }
+    }
+}
+
+/// Asks for the piece on promotion when auto-queen is turned off in the settings
+fn ask_promotion(config: Res<Config>, mut pending_promotion: ResMut<PendingPromotion>) {
+    if config.is_changed() {
+        pending_promotion.ask = !config.game.auto_queen;
+    }
+}
+
+/// Rebuild the buttons of the pieces the pawn can become while a promotion is waiting
+fn promotion_picker_update(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    materials: Res<MoveListMaterials>,
+    history: Res<History>,
+    pending_promotion: Res<PendingPromotion>,
+    picker_query: Query<Entity, With<PromotionPicker>>,
+    rows_query: Query<Entity, With<PromotionRow>>,
+) {
+    if !pending_promotion.is_changed() {
+        return;
+    }
+    for entity in rows_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let picker = match picker_query.iter().next() {
+        Some(picker) if pending_promotion.event.is_some() => picker,
+        _ => return,
+    };
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 20.0,
+        color: Color::rgb(0.8, 0.8, 0.8),
+    };
+
+    commands.entity(picker).with_children(|parent| {
+        parent
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Row,
+                    ..Default::default()
+                },
+                material: materials.none.clone(),
+                ..Default::default()
+            })
+            .insert(PromotionRow)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(110.), Val::Px(24.)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section("Promote to", text_style.clone(), Default::default()),
+                    ..Default::default()
+                });
+                for piece_type in history.rules().promotion_types() {
+                    let label = piece_letter(piece_type).unwrap_or('P').to_string();
+                    parent
+                        .spawn_bundle(ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(30.), Val::Px(24.)),
+                                padding: Rect {
+                                    left: Val::Px(5.),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            },
+                            material: materials.none.clone(),
+                            ..Default::default()
+                        })
+                        .insert(PromotionButton(piece_type))
+                        .with_children(|parent| {
+                            parent.spawn_bundle(TextBundle {
+                                text: Text::with_section(
+                                    label,
+                                    text_style.clone(),
+                                    Default::default(),
+                                ),
+                                ..Default::default()
+                            });
+                        });
+                }
+            });
+    });
+}
+
+/// Clicking a piece plays the waiting promotion with it, and Escape cancels the move
+fn promotion_buttons(
+    keyboard_input: Res<Input<KeyCode>>,
+    materials: Res<MoveListMaterials>,
+    mut pending_promotion: ResMut<PendingPromotion>,
+    mut move_piece: EventWriter<MovePieceEvent>,
+    mut query: Query<
+        (&Interaction, &PromotionButton, &mut Handle<ColorMaterial>),
+        Changed<Interaction>,
+    >,
+) {
+    let event = match pending_promotion.event {
+        Some(event) => event,
+        None => return,
+    };
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        pending_promotion.event = None;
+        return;
+    }
+    for (interaction, &PromotionButton(piece_type), mut material) in query.iter_mut() {
+        *material = match interaction {
+            Interaction::Clicked | Interaction::Hovered => materials.hovered.clone(),
+            Interaction::None => materials.none.clone(),
+        };
+        if *interaction == Interaction::Clicked {
+            pending_promotion.event = None;
+            move_piece.send(MovePieceEvent {
+                promotion: Some(piece_type),
+                ..event
+            });
+        }
+    }
+}
+
 /// Update text with the correct turn
 fn next_move_text_update(turn: Res<PlayerTurn>, mut query: Query<(&mut Text, &NextMoveText)>) {
     if !turn.is_changed() {
@@ -114,16 +539,269 @@ fn next_move_text_update(turn: Res<PlayerTurn>, mut query: Query<(&mut Text, &Ne
     }
 }
 
-fn last_turn_text_update(history: Res<History>, mut query: Query<(&mut Text, &LastTurnText)>) {
-    if !history.is_changed() {
+fn spawn_move_button(
+    parent: &mut ChildBuilder,
+    materials: &MoveListMaterials,
+    text_style: TextStyle,
+    san: &str,
+    index: usize,
+    current: bool,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(100.), Val::Px(24.)),
+                padding: Rect {
+                    left: Val::Px(5.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: if current {
+                materials.current.clone()
+            } else {
+                materials.none.clone()
+            },
+            ..Default::default()
+        })
+        .insert(MoveButton(index))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(san, text_style, Default::default()),
+                ..Default::default()
+            });
+        });
+}
+
+/// Rebuild the numbered White/Black rows shown in the move list
+#[allow(clippy::too_many_arguments)]
+fn move_list_update(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    materials: Res<MoveListMaterials>,
+    history: Res<History>,
+    browse: Res<Browse>,
+    scroll: Res<MoveListScroll>,
+    fog: Res<Fog>,
+    list_query: Query<Entity, With<MoveList>>,
+    rows_query: Query<Entity, With<MoveListRow>>,
+) {
+    if !history.is_changed() && !browse.is_changed() && !scroll.is_changed() && !fog.is_changed() {
         return;
     }
-    if history.turns.is_empty() {
+    for entity in rows_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let list = if let Some(list) = list_query.iter().next() {
+        list
+    } else {
         return;
+    };
+
+    let mut moves = san_moves(&history);
+    // Moves played in the fog are kept secret until the end
+    if let Some(viewer) = fog.viewer {
+        for (san, turn) in moves.iter_mut().zip(history.turns.iter()) {
+            if turn.color != viewer {
+                *san = "?".to_string();
+            }
+        }
     }
-    let last_turn = history.turns.last().unwrap();
-    for (mut text, _tag) in query.iter_mut() {
-        text.sections[0].value = last_turn.to_string();
+    let rows = (moves.len() + 1) / 2;
+    let first_row = scroll
+        .0
+        .unwrap_or_else(|| rows.saturating_sub(MOVE_LIST_ROWS))
+        .min(rows.saturating_sub(MOVE_LIST_ROWS));
+    let current = browse.0.or_else(|| moves.len().checked_sub(1));
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 20.0,
+        color: Color::rgb(0.8, 0.8, 0.8),
+    };
+
+    commands.entity(list).with_children(|parent| {
+        for row in first_row..(first_row + MOVE_LIST_ROWS).min(rows) {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        ..Default::default()
+                    },
+                    material: materials.none.clone(),
+                    ..Default::default()
+                })
+                .insert(MoveListRow)
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(40.), Val::Px(24.)),
+                            ..Default::default()
+                        },
+                        text: Text::with_section(
+                            format!("{}.", row + 1),
+                            text_style.clone(),
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    });
+                    for index in (row * 2)..(row * 2 + 2).min(moves.len()) {
+                        spawn_move_button(
+                            parent,
+                            &materials,
+                            text_style.clone(),
+                            &moves[index],
+                            index,
+                            Some(index) == current,
+                        );
+                    }
+                });
+        }
+    });
+}
+
+/// Scroll the move list with the mouse wheel, when the cursor is over it
+fn move_list_scroll(
+    windows: Res<Windows>,
+    history: Res<History>,
+    mut mouse_wheel_r: EventReader<MouseWheel>,
+    mut scroll: ResMut<MoveListScroll>,
+) {
+    let delta: f32 = mouse_wheel_r.iter().map(|event| event.y).sum();
+    if delta == 0. {
+        return;
+    }
+    let window = if let Some(window) = windows.get_primary() {
+        window
+    } else {
+        return;
+    };
+    match window.cursor_position() {
+        Some(cursor) if cursor.x > window.width() - MOVE_LIST_WIDTH - 10. => {}
+        _ => return,
+    }
+    let rows = (history.turns.len() + 1) / 2;
+    let last_first_row = rows.saturating_sub(MOVE_LIST_ROWS);
+    let first_row = scroll.0.unwrap_or(last_first_row);
+    let first_row = if delta > 0. {
+        first_row.saturating_sub(delta.ceil() as usize)
+    } else {
+        first_row + (-delta).ceil() as usize
+    };
+    // Scrolling to the bottom follows the game again
+    scroll.0 = if first_row >= last_first_row {
+        None
+    } else {
+        Some(first_row)
+    };
+}
+
+/// Clicking a move shows its position, clicking the latest one goes back to the game
+fn move_list_buttons(
+    materials: Res<MoveListMaterials>,
+    history: Res<History>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut browse: ResMut<Browse>,
+    mut move_query: Query<
+        (&Interaction, &MoveButton, &mut Handle<ColorMaterial>),
+        Changed<Interaction>,
+    >,
+    back_query: Query<&Interaction, (Changed<Interaction>, With<BackToGameButton>)>,
+) {
+    for (interaction, MoveButton(index), mut material) in move_query.iter_mut() {
+        let current = browse.0.or_else(|| history.turns.len().checked_sub(1)) == Some(*index);
+        match interaction {
+            Interaction::Clicked => {
+                browse.0 = if *index + 1 == history.turns.len() {
+                    None
+                } else {
+                    Some(*index)
+                };
+            }
+            Interaction::Hovered if !current => *material = materials.hovered.clone(),
+            Interaction::None if !current => *material = materials.none.clone(),
+            _ => {}
+        }
+    }
+    let back = back_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if (back || keyboard_input.just_pressed(KeyCode::Escape)) && browse.0.is_some() {
+        browse.0 = None;
+    }
+}
+
+fn back_to_game_visibility(
+    browse: Res<Browse>,
+    back_query: Query<&Children, With<BackToGameButton>>,
+    mut visible_query: Query<&mut Visible>,
+) {
+    if !browse.is_changed() {
+        return;
+    }
+    for children in back_query.iter() {
+        for child in children.iter() {
+            if let Ok(mut visible) = visible_query.get_mut(*child) {
+                visible.is_visible = browse.0.is_some();
+            }
+        }
+    }
+}
+
+/// Show the remaining time of both players, if there's a time control
+fn clock_text_update(clocks: Res<Clocks>, mut query: Query<&mut Text, With<ClockText>>) {
+    if !clocks.is_changed() {
+        return;
+    }
+    let value = if clocks.time_control.is_some() {
+        format!(
+            "White {}  Black {}",
+            format_clock(clocks.remaining(PieceColor::White)),
+            format_clock(clocks.remaining(PieceColor::Black))
+        )
+    } else {
+        String::new()
+    };
+    for mut text in query.iter_mut() {
+        // Clocks change every frame, but the text only every second
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+/// Show which side is ahead in material, like `White +3`
+fn material_text_update(captured: Res<Captured>, mut query: Query<&mut Text, With<MaterialText>>) {
+    if !captured.is_changed() {
+        return;
+    }
+    let balance = captured.balance(PieceColor::White);
+    let value = match balance {
+        0 => String::new(),
+        b if b > 0 => format!("White +{}", b),
+        b => format!("Black +{}", -b),
+    };
+    for mut text in query.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+/// Show the checks each side has given, in variants where they win the game
+fn checks_text_update(history: Res<History>, mut query: Query<&mut Text, With<ChecksText>>) {
+    if !history.is_changed() {
+        return;
+    }
+    let value = match history.rules().checks_to_win() {
+        Some(checks) => format!(
+            "Checks: White {}/{}  Black {}/{}",
+            history.checks_given(PieceColor::White),
+            checks,
+            history.checks_given(PieceColor::Black),
+            checks
+        ),
+        None => String::new(),
+    };
+    for mut text in query.iter_mut() {
+        text.sections[0].value = value.clone();
     }
 }
 
@@ -137,9 +815,22 @@ fn log_text_changes(query: Query<&Text, Changed<Text>>) {
 pub struct UIPlugin;
 impl Plugin for UIPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(init_next_move_text.system())
+        app.init_resource::<MoveListMaterials>()
+            .init_resource::<MoveListScroll>()
+            .add_startup_system(init_next_move_text.system())
             .add_system(next_move_text_update.system())
-            .add_system(last_turn_text_update.system())
+            .add_system(move_list_update.system())
+            .add_system(move_list_scroll.system())
+            .add_system(move_list_buttons.system())
+            .add_system(back_to_game_visibility.system())
+            .add_system(clock_text_update.system())
+            .add_system(material_text_update.system())
+            .add_system(checks_text_update.system())
+            .add_system(pockets_update.system())
+            .add_system(pocket_buttons.system())
+            .add_system(ask_promotion.system())
+            .add_system(promotion_picker_update.system())
+            .add_system(promotion_buttons.system().before("move_piece"))
             .add_system(log_text_changes.system());
     }
 }
@@ -0,0 +1,803 @@
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{EndReason, GameResult},
+    fen::chess960_fen,
+    history::History,
+    menu::GameSetup,
+    pieces::*,
+};
+
+/// Rules of a way to play. The board, the AI and the notation ask them which moves can be
+/// played, what a capture does and when the game is over
+pub trait Variant: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Value of the `Variant` tag in PGN, or `None` for games that are written as usual chess
+    fn pgn_name(&self) -> Option<&'static str> {
+        Some(self.name())
+    }
+
+    /// FEN of the position to start from, or `None` for the usual one
+    fn start_fen(&self, _setup: &GameSetup) -> Option<String> {
+        None
+    }
+
+    /// Moves `piece` can make on `pieces`
+    fn moves(&self, piece: &Piece, pieces: &[Piece], history: &History) -> Vec<MovePosition> {
+        piece.valid_positions(pieces, history)
+    }
+
+    /// Valid move of `piece` to `to`. Castling can also be asked for with the square the king
+    /// ends up on, when the king has no other move there
+    fn find_move(
+        &self,
+        piece: &Piece,
+        pieces: &[Piece],
+        history: &History,
+        to: (u8, u8),
+    ) -> Option<MovePosition> {
+        let moves = self.moves(piece, pieces, history);
+        moves
+            .iter()
+            .find(|&&(x, y, _)| (x, y) == to)
+            .or_else(|| {
+                moves.iter().find(|&&(x, y, _)| {
+                    is_castling(pieces, (piece.x, piece.y), (x, y))
+                        && (x, castling_files(piece.y, y).0) == to
+                })
+            })
+            .copied()
+    }
+
+    /// Squares of the pieces a capture landing on `square` removes besides the captured one.
+    /// `pieces` is the position before the move, with `capturer` still on its square
+    fn capture_effects(
+        &self,
+        _pieces: &[Piece],
+        _capturer: &Piece,
+        _square: (u8, u8),
+    ) -> Vec<(u8, u8)> {
+        Vec::new()
+    }
+
+    /// Squares `color` can see, or `None` when the whole board is in sight
+    fn visible_squares(
+        &self,
+        _pieces: &[Piece],
+        _history: &History,
+        _color: PieceColor,
+    ) -> Option<Vec<(u8, u8)>> {
+        None
+    }
+
+    /// Whether taken pieces go in hand, to be dropped on the board later
+    fn has_drops(&self) -> bool {
+        false
+    }
+
+    /// Whether kings in check are highlighted, which makes no sense when the king is a piece like
+    /// the others, or when its attackers may be hidden
+    fn has_check(&self) -> bool {
+        true
+    }
+
+    /// Squares the kings race to, highlighted on the board
+    fn goal_squares(&self) -> Vec<(u8, u8)> {
+        Vec::new()
+    }
+
+    /// Number of checks that wins the game, when checks are counted
+    fn checks_to_win(&self) -> Option<u8> {
+        None
+    }
+
+    /// How the game ended, if it's over, with `to_move` to play next
+    fn result(
+        &self,
+        pieces: &[Piece],
+        history: &History,
+        to_move: PieceColor,
+    ) -> Option<GameResult>;
+
+    /// Pieces a pawn can promote to, the first being the one it gets unless another is asked for
+    fn promotion_types(&self) -> Vec<PieceType> {
+        PROMOTION_TYPES.to_vec()
+    }
+
+    /// Plays a move on a plain list of pieces, returning every piece it removed. A pawn reaching
+    /// the last rank becomes `promotion`, or the first of the promotion types
+    fn play(
+        &self,
+        pieces: &mut Vec<Piece>,
+        from: (u8, u8),
+        mv: MovePosition,
+        promotion: Option<PieceType>,
+    ) -> Vec<Piece> {
+        let mover = pieces
+            .iter()
+            .find(|piece| (piece.x, piece.y) == from)
+            .copied();
+        let effects = match (mv.2, mover) {
+            (Some(_), Some(capturer)) => self.capture_effects(pieces, &capturer, (mv.0, mv.1)),
+            _ => Vec::new(),
+        };
+        let mut removed: Vec<Piece> = apply_move(pieces, from, mv).into_iter().collect();
+        for square in effects {
+            if let Some(index) = pieces.iter().position(|piece| (piece.x, piece.y) == square) {
+                removed.push(pieces.remove(index));
+            }
+        }
+        // The pawn may have been blown up with the piece it took
+        if let Some(mover) = mover.filter(|mover| promotes(mover, (mv.0, mv.1))) {
+            let types = self.promotion_types();
+            let piece_type = promotion
+                .filter(|piece_type| types.contains(piece_type))
+                .unwrap_or(types[0]);
+            if let Some(pawn) = pieces
+                .iter_mut()
+                .find(|piece| (piece.x, piece.y) == (mv.0, mv.1) && piece.color == mover.color)
+            {
+                pawn.piece_type = piece_type;
+                pawn.promoted = true;
+            }
+        }
+        removed
+    }
+}
+
+/// The game this started as: there's no check, and taking the king wins
+pub struct ClassicFun;
+
+impl Variant for ClassicFun {
+    fn name(&self) -> &'static str {
+        "Classic fun"
+    }
+
+    fn pgn_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn result(
+        &self,
+        pieces: &[Piece],
+        _history: &History,
+        _to_move: PieceColor,
+    ) -> Option<GameResult> {
+        king_taken(pieces)
+    }
+}
+
+/// Usual chess: moves can't leave the king in check, and the game ends in checkmate or
+/// stalemate
+pub struct Standard;
+
+impl Variant for Standard {
+    fn name(&self) -> &'static str {
+        "Standard"
+    }
+
+    fn pgn_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn moves(&self, piece: &Piece, pieces: &[Piece], history: &History) -> Vec<MovePosition> {
+        legal_moves(self, piece, pieces, history)
+    }
+
+    fn result(
+        &self,
+        pieces: &[Piece],
+        history: &History,
+        to_move: PieceColor,
+    ) -> Option<GameResult> {
+        no_moves_result(self, pieces, history, to_move)
+    }
+}
+
+/// Usual chess from a shuffled back rank, see `chess960_pieces`
+pub struct Chess960;
+
+impl Variant for Chess960 {
+    fn name(&self) -> &'static str {
+        "Chess960"
+    }
+
+    /// A random position unless one was picked
+    fn start_fen(&self, setup: &GameSetup) -> Option<String> {
+        let index = setup
+            .chess960_position
+            .unwrap_or_else(|| thread_rng().gen_range(0..CHESS960_POSITIONS));
+        Some(chess960_fen(index))
+    }
+
+    fn moves(&self, piece: &Piece, pieces: &[Piece], history: &History) -> Vec<MovePosition> {
+        legal_moves(self, piece, pieces, history)
+    }
+
+    fn result(
+        &self,
+        pieces: &[Piece],
+        history: &History,
+        to_move: PieceColor,
+    ) -> Option<GameResult> {
+        no_moves_result(self, pieces, history, to_move)
+    }
+}
+
+/// Captures explode, taking every piece but pawns around them along with the capturer. Kings
+/// can't capture, and blowing up the other king wins
+pub struct Atomic;
+
+impl Variant for Atomic {
+    fn name(&self) -> &'static str {
+        "Atomic"
+    }
+
+    fn moves(&self, piece: &Piece, pieces: &[Piece], history: &History) -> Vec<MovePosition> {
+        piece
+            .valid_positions(pieces, history)
+            .into_iter()
+            .filter(|&(_, _, takeable)| piece.piece_type != PieceType::King || takeable.is_none())
+            .filter(|&mv| {
+                let mut after = pieces.to_vec();
+                self.play(&mut after, (piece.x, piece.y), mv, None);
+                let king = find_king(&after, piece.color);
+                let other_king = find_king(&after, piece.color.opposite());
+                match (king, other_king) {
+                    (None, _) => false,
+                    (Some(_), None) => true,
+                    // Neither king can take the other, as it would blow up with it
+                    (Some(king), Some(other_king)) => {
+                        king_distance(king, other_king) <= 1
+                            || king_attackers(&after, history, piece.color).is_empty()
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn capture_effects(
+        &self,
+        pieces: &[Piece],
+        capturer: &Piece,
+        square: (u8, u8),
+    ) -> Vec<(u8, u8)> {
+        let mut effects = vec![square];
+        effects.extend(
+            pieces
+                .iter()
+                .filter(|piece| {
+                    piece.piece_type != PieceType::Pawn
+                        && (piece.x, piece.y) != (capturer.x, capturer.y)
+                        && (piece.x, piece.y) != square
+                        && king_distance((piece.x, piece.y), square) <= 1
+                })
+                .map(|piece| (piece.x, piece.y)),
+        );
+        effects
+    }
+
+    fn result(
+        &self,
+        pieces: &[Piece],
+        history: &History,
+        to_move: PieceColor,
+    ) -> Option<GameResult> {
+        king_taken(pieces)
+            .map(|result| GameResult {
+                reason: EndReason::KingExploded,
+                ..result
+            })
+            .or_else(|| no_moves_result(self, pieces, history, to_move))
+    }
+}
+
+/// Usual chess, also won by bringing the king to one of the four center squares
+pub struct KingOfTheHill;
+
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "King of the Hill"
+    }
+
+    fn moves(&self, piece: &Piece, pieces: &[Piece], history: &History) -> Vec<MovePosition> {
+        legal_moves(self, piece, pieces, history)
+    }
+
+    fn goal_squares(&self) -> Vec<(u8, u8)> {
+        vec![(3, 3), (3, 4), (4, 3), (4, 4)]
+    }
+
+    fn result(
+        &self,
+        pieces: &[Piece],
+        history: &History,
+        to_move: PieceColor,
+    ) -> Option<GameResult> {
+        let hill = self.goal_squares();
+        pieces
+            .iter()
+            .find(|piece| piece.piece_type == PieceType::King && hill.contains(&(piece.x, piece.y)))
+            .map(|king| GameResult {
+                winner: Some(king.color),
+                reason: EndReason::HillReached,
+            })
+            .or_else(|| no_moves_result(self, pieces, history, to_move))
+    }
+}
+
+/// Usual chess, also won by giving check three times
+pub struct ThreeCheck;
+
+impl Variant for ThreeCheck {
+    fn name(&self) -> &'static str {
+        "Three-check"
+    }
+
+    fn moves(&self, piece: &Piece, pieces: &[Piece], history: &History) -> Vec<MovePosition> {
+        legal_moves(self, piece, pieces, history)
+    }
+
+    fn checks_to_win(&self) -> Option<u8> {
+        Some(3)
+    }
+
+    fn result(
+        &self,
+        pieces: &[Piece],
+        history: &History,
+        to_move: PieceColor,
+    ) -> Option<GameResult> {
+        [PieceColor::White, PieceColor::Black]
+            .iter()
+            .find(|&&color| history.checks_given(color) >= 3)
+            .map(|&color| GameResult {
+                winner: Some(color),
+                reason: EndReason::ThreeChecks,
+            })
+            .or_else(|| no_moves_result(self, pieces, history, to_move))
+    }
+}
+
+/// Both kings race to the 8th rank from the first two ranks, and no move may give check. When
+/// White gets there first, Black still has a move to draw by getting there too
+pub struct RacingKings;
+
+impl Variant for RacingKings {
+    fn name(&self) -> &'static str {
+        "Racing Kings"
+    }
+
+    fn start_fen(&self, _setup: &GameSetup) -> Option<String> {
+        Some("8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1".to_string())
+    }
+
+    fn moves(&self, piece: &Piece, pieces: &[Piece], history: &History) -> Vec<MovePosition> {
+        legal_moves(self, piece, pieces, history)
+            .into_iter()
+            .filter(|&mv| {
+                let mut after = pieces.to_vec();
+                self.play(&mut after, (piece.x, piece.y), mv, None);
+                king_attackers(&after, history, piece.color.opposite()).is_empty()
+            })
+            .collect()
+    }
+
+    fn goal_squares(&self) -> Vec<(u8, u8)> {
+        (0..FIELD_SIZE).map(|y| (FIELD_SIZE - 1, y)).collect()
+    }
+
+    fn result(
+        &self,
+        pieces: &[Piece],
+        history: &History,
+        to_move: PieceColor,
+    ) -> Option<GameResult> {
+        let arrived = |color: PieceColor| {
+            find_king(pieces, color).map_or(false, |(x, _)| x == FIELD_SIZE - 1)
+        };
+        let winner = match (arrived(PieceColor::White), arrived(PieceColor::Black)) {
+            (true, true) => None,
+            (false, true) => Some(PieceColor::Black),
+            (true, false) => {
+                let black_can_follow = to_move == PieceColor::Black
+                    && pieces
+                        .iter()
+                        .filter(|piece| {
+                            piece.color == PieceColor::Black && piece.piece_type == PieceType::King
+                        })
+                        .flat_map(|king| self.moves(king, pieces, history))
+                        .any(|(x, _, _)| x == FIELD_SIZE - 1);
+                if black_can_follow {
+                    return None;
+                }
+                Some(PieceColor::White)
+            }
+            (false, false) => return no_moves_result(self, pieces, history, to_move),
+        };
+        Some(GameResult {
+            winner,
+            reason: EndReason::RaceWon,
+        })
+    }
+}
+
+fn find_king(pieces: &[Piece], color: PieceColor) -> Option<(u8, u8)> {
+    pieces
+        .iter()
+        .find(|piece| piece.color == color && piece.piece_type == PieceType::King)
+        .map(|piece| (piece.x, piece.y))
+}
+
+/// Number of king steps between two squares
+fn king_distance(a: (u8, u8), b: (u8, u8)) -> u8 {
+    let dx = (a.0 as i8 - b.0 as i8).abs();
+    let dy = (a.1 as i8 - b.1 as i8).abs();
+    dx.max(dy) as u8
+}
+
+/// Usual chess where taken pieces go in the captor's hand, and dropping one on an empty square
+/// is a move. Pawns can't be dropped on the first or last rank
+pub struct Crazyhouse;
+
+impl Variant for Crazyhouse {
+    fn name(&self) -> &'static str {
+        "Crazyhouse"
+    }
+
+    fn moves(&self, piece: &Piece, pieces: &[Piece], history: &History) -> Vec<MovePosition> {
+        if dropped_piece((piece.x, piece.y)).is_none() {
+            return legal_moves(self, piece, pieces, history);
+        }
+        let drops = (0..FIELD_SIZE)
+            .flat_map(|x| (0..FIELD_SIZE).map(move |y| (x, y, None)))
+            .filter(|&(x, y, _)| {
+                !pieces.iter().any(|other| (other.x, other.y) == (x, y))
+                    && !(piece.piece_type == PieceType::Pawn && (x == 0 || x == FIELD_SIZE - 1))
+            })
+            .collect();
+        keep_legal(self, piece, pieces, history, drops)
+    }
+
+    fn has_drops(&self) -> bool {
+        true
+    }
+
+    fn result(
+        &self,
+        pieces: &[Piece],
+        history: &History,
+        to_move: PieceColor,
+    ) -> Option<GameResult> {
+        no_moves_result(self, pieces, history, to_move)
+    }
+}
+
+/// Each side only sees the squares its pieces stand on or can move to. There's no check, and
+/// taking the king wins
+pub struct FogOfWar;
+
+impl Variant for FogOfWar {
+    fn name(&self) -> &'static str {
+        "Fog of War"
+    }
+
+    /// The piece attacking the king may be in the fog
+    fn has_check(&self) -> bool {
+        false
+    }
+
+    fn visible_squares(
+        &self,
+        pieces: &[Piece],
+        history: &History,
+        color: PieceColor,
+    ) -> Option<Vec<(u8, u8)>> {
+        let mut visible = Vec::new();
+        for piece in pieces.iter().filter(|piece| piece.color == color) {
+            visible.push((piece.x, piece.y));
+            for (x, y, takeable) in self.moves(piece, pieces, history) {
+                visible.push((x, y));
+                // The pawn taken en passant is seen too
+                if let Some(Takeable(x, y)) = takeable {
+                    visible.push((x, y));
+                }
+            }
+        }
+        visible.sort_unstable();
+        visible.dedup();
+        Some(visible)
+    }
+
+    fn result(
+        &self,
+        pieces: &[Piece],
+        _history: &History,
+        _to_move: PieceColor,
+    ) -> Option<GameResult> {
+        king_taken(pieces)
+    }
+}
+
+/// Losing chess: whoever has no pieces or no moves left wins. Captures must be made when there
+/// are any, the king is a piece like the others, pawns can promote to one and there's no
+/// castling
+pub struct Antichess;
+
+impl Antichess {
+    /// Moves of `piece`, taking or not, castling left out
+    fn all_moves(piece: &Piece, pieces: &[Piece], history: &History) -> Vec<MovePosition> {
+        piece
+            .valid_positions(pieces, history)
+            .into_iter()
+            .filter(|&(x, y, _)| !is_castling(pieces, (piece.x, piece.y), (x, y)))
+            .collect()
+    }
+}
+
+impl Variant for Antichess {
+    fn name(&self) -> &'static str {
+        "Antichess"
+    }
+
+    fn has_check(&self) -> bool {
+        false
+    }
+
+    fn promotion_types(&self) -> Vec<PieceType> {
+        let mut types = PROMOTION_TYPES.to_vec();
+        types.push(PieceType::King);
+        types
+    }
+
+    fn moves(&self, piece: &Piece, pieces: &[Piece], history: &History) -> Vec<MovePosition> {
+        let moves = Antichess::all_moves(piece, pieces, history);
+        let must_take = pieces
+            .iter()
+            .filter(|other| other.color == piece.color)
+            .any(|other| {
+                Antichess::all_moves(other, pieces, history)
+                    .iter()
+                    .any(|&(_, _, takeable)| takeable.is_some())
+            });
+        if !must_take {
+            return moves;
+        }
+        moves
+            .into_iter()
+            .filter(|&(_, _, takeable)| takeable.is_some())
+            .collect()
+    }
+
+    fn result(
+        &self,
+        pieces: &[Piece],
+        history: &History,
+        to_move: PieceColor,
+    ) -> Option<GameResult> {
+        // Pieces only disappear on the opponent's move, so it's the side to move that can run out
+        if !pieces.iter().any(|piece| piece.color == to_move) {
+            return Some(GameResult {
+                winner: Some(to_move),
+                reason: EndReason::PiecesGone,
+            });
+        }
+        if pieces
+            .iter()
+            .filter(|piece| piece.color == to_move)
+            .all(|piece| self.moves(piece, pieces, history).is_empty())
+        {
+            return Some(GameResult {
+                winner: Some(to_move),
+                reason: EndReason::Stalemate,
+            });
+        }
+        None
+    }
+}
+
+/// 36 white pawns against the usual black army. White wins by checkmate, Black by taking the
+/// whole horde
+pub struct Horde;
+
+impl Variant for Horde {
+    fn name(&self) -> &'static str {
+        "Horde"
+    }
+
+    fn start_fen(&self, _setup: &GameSetup) -> Option<String> {
+        Some(
+            "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1"
+                .to_string(),
+        )
+    }
+
+    fn moves(&self, piece: &Piece, pieces: &[Piece], history: &History) -> Vec<MovePosition> {
+        legal_moves(self, piece, pieces, history)
+    }
+
+    fn result(
+        &self,
+        pieces: &[Piece],
+        history: &History,
+        to_move: PieceColor,
+    ) -> Option<GameResult> {
+        if !pieces.iter().any(|piece| piece.color == PieceColor::White) {
+            return Some(GameResult {
+                winner: Some(PieceColor::Black),
+                reason: EndReason::HordeTaken,
+            });
+        }
+        // White has no king, so being stuck is only ever a stalemate for it
+        no_moves_result(self, pieces, history, to_move)
+    }
+}
+
+/// Moves of `piece` that don't leave its king where it could be taken
+pub fn legal_moves(
+    rules: &dyn Variant,
+    piece: &Piece,
+    pieces: &[Piece],
+    history: &History,
+) -> Vec<MovePosition> {
+    keep_legal(
+        rules,
+        piece,
+        pieces,
+        history,
+        piece.valid_positions(pieces, history),
+    )
+}
+
+/// The `moves` of `piece` that don't leave its king where it could be taken
+pub fn keep_legal(
+    rules: &dyn Variant,
+    piece: &Piece,
+    pieces: &[Piece],
+    history: &History,
+    moves: Vec<MovePosition>,
+) -> Vec<MovePosition> {
+    moves
+        .into_iter()
+        .filter(|&mv| {
+            let mut after = pieces.to_vec();
+            rules.play(&mut after, (piece.x, piece.y), mv, None);
+            king_attackers(&after, history, piece.color).is_empty()
+        })
+        .collect()
+}
+
+/// Checkmate or stalemate, once `to_move` has no moves left
+pub fn no_moves_result(
+    rules: &dyn Variant,
+    pieces: &[Piece],
+    history: &History,
+    to_move: PieceColor,
+) -> Option<GameResult> {
+    if movable_pieces(rules, pieces, history, to_move)
+        .iter()
+        .any(|piece| !rules.moves(piece, pieces, history).is_empty())
+    {
+        return None;
+    }
+    Some(if king_attackers(pieces, history, to_move).is_empty() {
+        GameResult {
+            winner: None,
+            reason: EndReason::Stalemate,
+        }
+    } else {
+        GameResult {
+            winner: Some(to_move.opposite()),
+            reason: EndReason::Checkmate,
+        }
+    })
+}
+
+/// Pieces of `color` that may have moves: those on the board, and one of each type in hand
+/// in drop variants
+pub fn movable_pieces(
+    rules: &dyn Variant,
+    pieces: &[Piece],
+    history: &History,
+    color: PieceColor,
+) -> Vec<Piece> {
+    let mut movable: Vec<Piece> = pieces
+        .iter()
+        .filter(|piece| piece.color == color)
+        .copied()
+        .collect();
+    if rules.has_drops() {
+        let pocket = history.pocket(color);
+        for piece_type in PIECE_TYPES
+            .iter()
+            .filter(|piece_type| pocket.contains(piece_type))
+        {
+            movable.push(Piece::in_pocket(color, *piece_type));
+        }
+    }
+    movable
+}
+
+/// Win for the side whose opponent has lost its king
+pub fn king_taken(pieces: &[Piece]) -> Option<GameResult> {
+    [PieceColor::White, PieceColor::Black]
+        .iter()
+        .find(|&&color| {
+            !pieces
+                .iter()
+                .any(|piece| piece.color == color && piece.piece_type == PieceType::King)
+        })
+        .map(|color| GameResult {
+            winner: Some(color.opposite()),
+            reason: EndReason::KingTaken,
+        })
+}
+
+/// Rules picked for a game, kept in the setup and the history
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum GameVariant {
+    #[serde(alias = "Classic")]
+    ClassicFun,
+    Standard,
+    Chess960,
+    Atomic,
+    KingOfTheHill,
+    ThreeCheck,
+    RacingKings,
+    Crazyhouse,
+    FogOfWar,
+    Antichess,
+    Horde,
+}
+
+impl Default for GameVariant {
+    fn default() -> Self {
+        GameVariant::ClassicFun
+    }
+}
+
+impl GameVariant {
+    pub const ALL: [GameVariant; 11] = [
+        GameVariant::ClassicFun,
+        GameVariant::Standard,
+        GameVariant::Chess960,
+        GameVariant::Atomic,
+        GameVariant::KingOfTheHill,
+        GameVariant::ThreeCheck,
+        GameVariant::RacingKings,
+        GameVariant::Crazyhouse,
+        GameVariant::FogOfWar,
+        GameVariant::Antichess,
+        GameVariant::Horde,
+    ];
+
+    pub fn rules(&self) -> &'static dyn Variant {
+        match self {
+            GameVariant::ClassicFun => &ClassicFun,
+            GameVariant::Standard => &Standard,
+            GameVariant::Chess960 => &Chess960,
+            GameVariant::Atomic => &Atomic,
+            GameVariant::KingOfTheHill => &KingOfTheHill,
+            GameVariant::ThreeCheck => &ThreeCheck,
+            GameVariant::RacingKings => &RacingKings,
+            GameVariant::Crazyhouse => &Crazyhouse,
+            GameVariant::FogOfWar => &FogOfWar,
+            GameVariant::Antichess => &Antichess,
+            GameVariant::Horde => &Horde,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.rules().name()
+    }
+
+    /// Variant named in a PGN `Variant` tag
+    pub fn from_pgn_name(name: &str) -> Option<Self> {
+        GameVariant::ALL
+            .iter()
+            .copied()
+            .find(|variant| variant.rules().pgn_name() == Some(name))
+    }
+}
@@ -0,0 +1,237 @@
+//! Runs the game plugins without a window, one frame at a time, so tests can play moves and look
+//! at the resulting state
+
+use std::{
+    env, fs, process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use bevy::prelude::*;
+use bevy_chess::{
+    board::{
+        GameResult, MovableSquare, MovePieceEvent, NewGameEvent, PlayerTurn, SelectedPiece, Square,
+        Taken,
+    },
+    captured::Captured,
+    config::ConfigPath,
+    headless::Headless,
+    history::History,
+    menu::{AppState, GameSetup},
+    notation::parse_square,
+    pieces::{pocket_square, Piece, PieceColor, PieceType},
+    save::{LoadGameEvent, SaveGame},
+    GamePlugins,
+};
+
+/// Numbers the config files of the games, so the tests running at once don't share one
+static CONFIG_FILES: AtomicUsize = AtomicUsize::new(0);
+
+pub struct TestGame {
+    pub app: App,
+}
+
+impl TestGame {
+    /// Game from the usual position, both sides played from this computer
+    pub fn new() -> Self {
+        Self::with_setup(GameSetup::default())
+    }
+
+    pub fn from_fen(fen: &str) -> Self {
+        Self::with_setup(GameSetup {
+            fen: Some(fen.to_string()),
+            ..Default::default()
+        })
+    }
+
+    pub fn with_setup(setup: GameSetup) -> Self {
+        let mut game = Self::in_main_menu(setup);
+        game.send(NewGameEvent);
+        game.enter_game();
+        game
+    }
+
+    /// Plays on from a saved game
+    pub fn load(save: SaveGame) -> Self {
+        let mut game = Self::in_main_menu(save.setup.clone());
+        game.send(LoadGameEvent(save));
+        game.enter_game();
+        game
+    }
+
+    fn in_main_menu(setup: GameSetup) -> Self {
+        let mut builder = App::build();
+        // Settings closed in a test are saved away from the player's config
+        let config_file = format!(
+            "bevy_chess_test_{}_{}.toml",
+            process::id(),
+            CONFIG_FILES.fetch_add(1, Ordering::Relaxed)
+        );
+        builder
+            .insert_resource(Headless::default())
+            .insert_resource(ConfigPath(env::temp_dir().join(config_file)))
+            .insert_resource(setup)
+            .add_plugins(MinimalPlugins)
+            .add_plugins(GamePlugins);
+        let mut game = TestGame { app: builder.app };
+        // Runs the startup systems
+        game.update();
+        game
+    }
+
+    fn enter_game(&mut self) {
+        self.resource_mut::<State<AppState>>()
+            .set(AppState::InGame)
+            .unwrap();
+        self.update();
+    }
+
+    pub fn update(&mut self) {
+        self.app.update();
+    }
+
+    pub fn send<T: Send + Sync + 'static>(&mut self, event: T) {
+        self.resource_mut::<Events<T>>().send(event);
+    }
+
+    pub fn resource<T: Send + Sync + 'static>(&self) -> &T {
+        self.app
+            .world
+            .get_resource::<T>()
+            .expect("missing resource")
+    }
+
+    pub fn resource_mut<T: Send + Sync + 'static>(&mut self) -> Mut<T> {
+        self.app
+            .world
+            .get_resource_mut::<T>()
+            .expect("missing resource")
+    }
+
+    /// Sends the move from one square to the other, like `play("e2", "e4")`, and runs the frame
+    /// playing it
+    pub fn play(&mut self, from: &str, to: &str) {
+        self.send(MovePieceEvent {
+            from: square(from),
+            to: square(to),
+            promotion: None,
+        });
+        self.update();
+    }
+
+    /// Plays a pawn to the last rank, asking for it to become `piece_type`
+    pub fn promote(&mut self, from: &str, to: &str, piece_type: PieceType) {
+        self.send(MovePieceEvent {
+            from: square(from),
+            to: square(to),
+            promotion: Some(piece_type),
+        });
+        self.update();
+    }
+
+    /// Drops a piece in hand of the side to move on `to`, and runs the frame playing it
+    pub fn drop_piece(&mut self, piece_type: PieceType, to: &str) {
+        self.send(MovePieceEvent {
+            from: pocket_square(self.turn(), piece_type),
+            to: square(to),
+            promotion: None,
+        });
+        self.update();
+    }
+
+    /// Selects the piece on `name` as if it had been clicked
+    pub fn select(&mut self, name: &str) {
+        let (x, y) = square(name);
+        let piece_entity = self
+            .app
+            .world
+            .query_filtered::<(Entity, &Piece), Without<Taken>>()
+            .iter(&self.app.world)
+            .find(|(_, piece)| (piece.x, piece.y) == (x, y))
+            .map(|(entity, _)| entity)
+            .expect("no piece to select");
+        // There are no squares without a window, the piece stands in for it
+        *self.resource_mut::<Option<SelectedPiece>>() = Some(SelectedPiece {
+            square_entity: piece_entity,
+            piece_entity,
+            x,
+            y,
+        });
+    }
+
+    pub fn selected(&self) -> Option<(u8, u8)> {
+        self.resource::<Option<SelectedPiece>>()
+            .as_ref()
+            .map(|selected| (selected.x, selected.y))
+    }
+
+    /// Squares highlighted for the selected piece
+    pub fn movable_squares(&mut self) -> Vec<(u8, u8)> {
+        self.app
+            .world
+            .query_filtered::<&Square, With<MovableSquare>>()
+            .iter(&self.app.world)
+            .map(|square| (square.x, square.y))
+            .collect()
+    }
+
+    /// Pieces still on the board
+    pub fn pieces(&mut self) -> Vec<Piece> {
+        self.app
+            .world
+            .query_filtered::<&Piece, Without<Taken>>()
+            .iter(&self.app.world)
+            .copied()
+            .collect()
+    }
+
+    pub fn piece_at(&mut self, name: &str) -> Option<Piece> {
+        let square = square(name);
+        self.pieces()
+            .into_iter()
+            .find(|piece| (piece.x, piece.y) == square)
+    }
+
+    pub fn turn(&self) -> PieceColor {
+        self.resource::<PlayerTurn>().0
+    }
+
+    pub fn history(&self) -> &History {
+        self.resource::<History>()
+    }
+
+    pub fn captured(&self) -> &Captured {
+        self.resource::<Captured>()
+    }
+
+    pub fn state(&self) -> AppState {
+        *self.resource::<State<AppState>>().current()
+    }
+
+    /// Opens the settings over the game, like `F10`, and runs the frame entering them
+    pub fn open_settings(&mut self) {
+        self.resource_mut::<State<AppState>>()
+            .push(AppState::Settings)
+            .unwrap();
+        self.update();
+    }
+
+    /// Goes back from the settings, and runs the frame leaving them
+    pub fn close_settings(&mut self) {
+        self.resource_mut::<State<AppState>>().pop().unwrap();
+        self.update();
+    }
+
+    pub fn result(&self) -> Option<GameResult> {
+        *self.resource::<Option<GameResult>>()
+    }
+}
+
+impl Drop for TestGame {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.resource::<ConfigPath>().0);
+    }
+}
+
+pub fn square(name: &str) -> (u8, u8) {
+    parse_square(name).unwrap_or_else(|| panic!("{} isn't a square", name))
+}
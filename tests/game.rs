@@ -0,0 +1,748 @@
+mod common;
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader},
+    net::{TcpListener, TcpStream},
+    process, thread,
+    time::Duration,
+};
+
+use bevy_chess::{
+    ai,
+    annotations::{AnnotationColor, Annotations, PositionAnnotations},
+    board::{EndReason, GameResult, MovePieceEvent, PendingPromotion, Square},
+    cli::{Args, Launch},
+    clock::{Clocks, TimeControl},
+    config::{read_config, Config, ConfigPath},
+    fen::{parse_fen, to_fen},
+    fog::Fog,
+    history::History,
+    keyboard::is_move_char,
+    menu::{AppState, GameSetup, OpponentKind},
+    network::{Message, Peer},
+    notation::{parse_move, san_moves},
+    pgn::export_pgn,
+    pieces::{PieceColor, PieceType},
+    save::SaveGame,
+    variant::GameVariant,
+};
+use common::{square, TestGame};
+use structopt::StructOpt;
+
+#[test]
+fn move_updates_piece_history_and_turn() {
+    let mut game = TestGame::new();
+    assert_eq!(game.state(), AppState::InGame);
+    assert_eq!(game.pieces().len(), 32);
+    assert_eq!(game.turn(), PieceColor::White);
+
+    game.play("e2", "e4");
+    let pawn = game.piece_at("e4").expect("pawn didn't move");
+    assert_eq!(pawn.piece_type, PieceType::Pawn);
+    assert_eq!(pawn.color, PieceColor::White);
+    assert!(game.piece_at("e2").is_none());
+    assert_eq!(game.history().turns.len(), 1);
+    let turn = game.history().turns[0];
+    assert_eq!((turn.from_x, turn.from_y), square("e2"));
+    assert_eq!((turn.to_x, turn.to_y), square("e4"));
+    assert_eq!(game.turn(), PieceColor::Black);
+}
+
+#[test]
+fn invalid_moves_are_ignored() {
+    let mut game = TestGame::new();
+    game.play("e2", "e5");
+    // Black's pieces can't be moved on White's turn
+    game.play("e7", "e5");
+    assert!(game.piece_at("e2").is_some());
+    assert!(game.piece_at("e7").is_some());
+    assert!(game.history().turns.is_empty());
+    assert_eq!(game.turn(), PieceColor::White);
+}
+
+#[test]
+fn capture_removes_piece() {
+    let mut game = TestGame::new();
+    game.play("e2", "e4");
+    game.play("d7", "d5");
+    game.play("e4", "d5");
+    game.update();
+    let pawn = game.piece_at("d5").unwrap();
+    assert_eq!(pawn.color, PieceColor::White);
+    assert_eq!(game.pieces().len(), 31);
+    assert_eq!(game.captured().by(PieceColor::White), &[PieceType::Pawn]);
+    assert!(game.captured().by(PieceColor::Black).is_empty());
+}
+
+#[test]
+fn taking_king_ends_game() {
+    let mut game = TestGame::from_fen("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1");
+    game.play("e1", "e8");
+    // The king is despawned, then the game ends
+    for _ in 0..3 {
+        game.update();
+    }
+    assert_eq!(game.state(), AppState::GameOver);
+    let result = game.result().expect("no result");
+    assert_eq!(result.winner, Some(PieceColor::White));
+    assert_eq!(result.reason, EndReason::KingTaken);
+}
+
+#[test]
+fn selection_is_reset_after_move() {
+    let mut game = TestGame::new();
+    game.select("g1");
+    game.update();
+    assert_eq!(game.selected(), Some(square("g1")));
+    game.play("g1", "f3");
+    assert_eq!(game.selected(), None);
+    assert_eq!(
+        game.piece_at("f3").map(|piece| piece.piece_type),
+        Some(PieceType::Knight)
+    );
+}
+
+#[test]
+fn nothing_stays_highlighted_after_a_move() {
+    let mut game = TestGame::new();
+    // There are no squares without a window
+    for x in 0..8 {
+        for y in 0..8 {
+            game.app.world.spawn().insert(Square { x, y });
+        }
+    }
+    game.select("g1");
+    game.update();
+    assert_eq!(game.movable_squares().len(), 2);
+    // Moves are played before the squares of a new selection are highlighted
+    game.select("b1");
+    game.play("b1", "c3");
+    assert!(game.movable_squares().is_empty());
+}
+
+/// Plays a move as if it had been typed and submitted with Enter
+fn type_move(game: &mut TestGame, text: &str) {
+    let typed: String = text.chars().filter(|&c| is_move_char(c)).collect();
+    let pieces = game.pieces();
+    let event = parse_move(&pieces, game.history(), game.turn(), &typed)
+        .unwrap_or_else(|| panic!("{} isn't a valid move", text));
+    game.send(event);
+    game.update();
+}
+
+#[test]
+fn castling_can_be_typed() {
+    let mut game = variant_from_fen(GameVariant::Standard, "r3k3/8/8/8/8/8/8/4K2R w Kq - 0 1");
+    type_move(&mut game, "O-O");
+    assert_eq!(game.piece_at("g1").unwrap().piece_type, PieceType::King);
+    assert_eq!(game.piece_at("f1").unwrap().piece_type, PieceType::Rook);
+    type_move(&mut game, "0-0-0");
+    assert_eq!(game.piece_at("c8").unwrap().piece_type, PieceType::King);
+    assert_eq!(game.piece_at("d8").unwrap().piece_type, PieceType::Rook);
+}
+
+#[test]
+fn fen_sets_side_to_move() {
+    let mut game =
+        TestGame::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    assert_eq!(game.turn(), PieceColor::Black);
+    assert!(game.piece_at("e4").is_some());
+    game.play("e7", "e5");
+    assert!(game.piece_at("e5").is_some());
+    assert_eq!(game.turn(), PieceColor::White);
+}
+
+#[test]
+fn loaded_game_continues() {
+    let mut game = TestGame::new();
+    game.play("e2", "e4");
+    let save = SaveGame::from_history(game.history().clone(), GameSetup::default());
+
+    let mut game = TestGame::load(save);
+    assert_eq!(game.turn(), PieceColor::Black);
+    assert!(game.piece_at("e4").is_some());
+    assert_eq!(game.pieces().len(), 32);
+    game.play("c7", "c5");
+    assert_eq!(game.history().turns.len(), 2);
+    assert_eq!(game.turn(), PieceColor::White);
+}
+
+#[test]
+fn history_read_back_starts_from_its_fen() {
+    let game = TestGame::from_fen("4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+    let saved = ron::to_string(game.history()).unwrap();
+    let history: History = ron::from_str(&saved).unwrap();
+    assert_eq!(history.fen(), Some("4k3/8/8/8/8/8/8/4K3 b - - 0 1"));
+    assert_eq!(history.start().turn, PieceColor::Black);
+    assert_eq!(history.start().pieces.len(), 2);
+}
+
+#[test]
+fn loaded_game_only_counts_the_pieces_taken_in_it() {
+    let mut game = TestGame::from_fen("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1");
+    game.play("e1", "d2");
+    let save = SaveGame::from_history(game.history().clone(), GameSetup::default());
+
+    let game = TestGame::load(save);
+    assert_eq!(game.captured().by(PieceColor::White), &[PieceType::Pawn]);
+    assert!(game.captured().by(PieceColor::Black).is_empty());
+}
+
+#[test]
+fn castling_moves_king_and_rook() {
+    let mut game = TestGame::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    // Asked for with the square the king ends up on
+    game.play("e1", "g1");
+    assert_eq!(game.piece_at("g1").unwrap().piece_type, PieceType::King);
+    assert_eq!(game.piece_at("f1").unwrap().piece_type, PieceType::Rook);
+    assert!(game.piece_at("h1").is_none());
+    // Recorded as the king going to its rook
+    let turn = game.history().turns[0];
+    assert_eq!((turn.to_x, turn.to_y), square("h1"));
+
+    // Or with the rook's square
+    game.play("e8", "a8");
+    assert_eq!(game.piece_at("c8").unwrap().piece_type, PieceType::King);
+    assert_eq!(game.piece_at("d8").unwrap().piece_type, PieceType::Rook);
+}
+
+#[test]
+fn castling_needs_unmoved_pieces() {
+    let mut game = TestGame::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Qkq - 0 1");
+    game.play("e1", "h1");
+    assert_eq!(game.piece_at("e1").unwrap().piece_type, PieceType::King);
+    assert!(game.history().turns.is_empty());
+}
+
+#[test]
+fn chess960_castling_lands_on_usual_squares() {
+    let mut game = TestGame::from_fen("1r4kr/pppppppp/8/8/8/8/PPPPPPPP/1R4KR w BHbh - 0 1");
+    game.play("g1", "b1");
+    assert_eq!(game.piece_at("c1").unwrap().piece_type, PieceType::King);
+    assert_eq!(game.piece_at("d1").unwrap().piece_type, PieceType::Rook);
+    // The king is already on its castling square on this side
+    game.play("g8", "h8");
+    assert_eq!(game.piece_at("g8").unwrap().piece_type, PieceType::King);
+    assert_eq!(game.piece_at("f8").unwrap().piece_type, PieceType::Rook);
+}
+
+#[test]
+fn chess960_starts_from_numbered_position() {
+    use PieceType::*;
+    let mut game = TestGame::with_setup(GameSetup {
+        variant: GameVariant::Chess960,
+        chess960_position: Some(0),
+        ..Default::default()
+    });
+    let back_rank: Vec<PieceType> = ["a1", "b1", "c1", "d1", "e1", "f1", "g1", "h1"]
+        .iter()
+        .map(|name| game.piece_at(name).unwrap().piece_type)
+        .collect();
+    assert_eq!(
+        back_rank,
+        [Bishop, Bishop, Queen, Knight, Knight, Rook, King, Rook]
+    );
+    assert_eq!(
+        game.history().fen(),
+        Some("bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1")
+    );
+}
+
+fn variant_from_fen(variant: GameVariant, fen: &str) -> TestGame {
+    TestGame::with_setup(GameSetup {
+        fen: Some(fen.to_string()),
+        variant,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn standard_rules_forbid_moving_into_check() {
+    let mut game = variant_from_fen(GameVariant::Standard, "4k3/8/8/8/8/8/4r3/4K3 w - - 0 1");
+    game.play("e1", "d2");
+    assert!(game.piece_at("e1").is_some());
+    assert!(game.history().turns.is_empty());
+    game.play("e1", "e2");
+    assert_eq!(game.piece_at("e2").unwrap().color, PieceColor::White);
+}
+
+#[test]
+fn checkmate_ends_standard_game() {
+    let mut game = TestGame::with_setup(GameSetup {
+        variant: GameVariant::Standard,
+        ..Default::default()
+    });
+    game.play("f2", "f3");
+    game.play("e7", "e5");
+    game.play("g2", "g4");
+    game.play("d8", "h4");
+    for _ in 0..3 {
+        game.update();
+    }
+    assert_eq!(game.state(), AppState::GameOver);
+    let result = game.result().expect("no result");
+    assert_eq!(result.winner, Some(PieceColor::Black));
+    assert_eq!(result.reason, EndReason::Checkmate);
+}
+
+#[test]
+fn stalemate_is_a_draw() {
+    let mut game = variant_from_fen(GameVariant::Standard, "7k/8/5K2/6Q1/8/8/8/8 w - - 0 1");
+    game.play("g5", "g6");
+    for _ in 0..3 {
+        game.update();
+    }
+    let result = game.result().expect("no result");
+    assert_eq!(result.winner, None);
+    assert_eq!(result.reason, EndReason::Stalemate);
+}
+
+#[test]
+fn atomic_capture_blows_up_neighbours() {
+    let mut game = variant_from_fen(GameVariant::Atomic, "4k3/8/3bp3/4n3/3P4/8/8/4K3 w - - 0 1");
+    game.play("d4", "e5");
+    game.update();
+    // The capturer and the bishop go with the knight, the pawn is spared
+    assert!(game.piece_at("e5").is_none());
+    assert!(game.piece_at("d4").is_none());
+    assert!(game.piece_at("d6").is_none());
+    assert_eq!(game.piece_at("e6").unwrap().piece_type, PieceType::Pawn);
+    assert_eq!(game.history().turns.len(), 1);
+}
+
+#[test]
+fn atomic_kings_cannot_capture() {
+    let mut game = variant_from_fen(GameVariant::Atomic, "4k3/8/8/8/8/8/3p4/4K3 w - - 0 1");
+    game.play("e1", "d2");
+    assert!(game.history().turns.is_empty());
+    assert_eq!(game.piece_at("d2").unwrap().color, PieceColor::Black);
+}
+
+#[test]
+fn atomic_blowing_up_king_wins() {
+    let mut game = variant_from_fen(GameVariant::Atomic, "4k3/4q3/8/8/8/8/8/4RK2 w - - 0 1");
+    game.play("e1", "e7");
+    for _ in 0..3 {
+        game.update();
+    }
+    assert_eq!(game.state(), AppState::GameOver);
+    let result = game.result().expect("no result");
+    assert_eq!(result.winner, Some(PieceColor::White));
+    assert_eq!(result.reason, EndReason::KingExploded);
+}
+
+fn run_to_result(game: &mut TestGame) -> GameResult {
+    for _ in 0..3 {
+        game.update();
+    }
+    game.result().expect("no result")
+}
+
+#[test]
+fn king_of_the_hill_is_won_in_the_center() {
+    let mut game = variant_from_fen(GameVariant::KingOfTheHill, "4k3/8/8/8/8/4K3/8/8 w - - 0 1");
+    game.play("e3", "e4");
+    let result = run_to_result(&mut game);
+    assert_eq!(result.winner, Some(PieceColor::White));
+    assert_eq!(result.reason, EndReason::HillReached);
+}
+
+#[test]
+fn three_check_counts_checks_from_fen() {
+    // White has one check left to give
+    let mut game = variant_from_fen(
+        GameVariant::ThreeCheck,
+        "4k3/8/8/8/8/8/8/R3K3 w - - 1+3 0 1",
+    );
+    assert_eq!(game.history().checks_given(PieceColor::White), 2);
+    game.play("a1", "a8");
+    assert!(game.history().turns[0].check);
+    let result = run_to_result(&mut game);
+    assert_eq!(result.winner, Some(PieceColor::White));
+    assert_eq!(result.reason, EndReason::ThreeChecks);
+}
+
+#[test]
+fn racing_kings_forbids_checks() {
+    let mut game = TestGame::with_setup(GameSetup {
+        variant: GameVariant::RacingKings,
+        ..Default::default()
+    });
+    assert_eq!(game.pieces().len(), 16);
+    assert_eq!(game.piece_at("h2").unwrap().piece_type, PieceType::King);
+    let mut game = variant_from_fen(GameVariant::RacingKings, "8/8/8/8/8/8/k7/6RK w - - 0 1");
+    game.play("g1", "a1");
+    assert!(game.history().turns.is_empty());
+}
+
+#[test]
+fn racing_kings_lets_black_catch_up() {
+    let mut game = variant_from_fen(GameVariant::RacingKings, "8/1k4K1/8/8/8/8/8/8 w - - 0 1");
+    game.play("g7", "g8");
+    game.update();
+    assert!(game.result().is_none());
+    game.play("b7", "b8");
+    let result = run_to_result(&mut game);
+    assert_eq!(result.winner, None);
+    assert_eq!(result.reason, EndReason::RaceWon);
+}
+
+#[test]
+fn crazyhouse_captures_can_be_dropped() {
+    let mut game = variant_from_fen(GameVariant::Crazyhouse, "4k3/8/8/8/8/1p6/8/N3K3 w - - 0 1");
+    game.play("a1", "b3");
+    assert_eq!(game.history().pocket(PieceColor::White), [PieceType::Pawn]);
+    game.play("e8", "d8");
+    game.drop_piece(PieceType::Pawn, "e4");
+    let pawn = game.piece_at("e4").expect("pawn wasn't dropped");
+    assert_eq!(pawn.piece_type, PieceType::Pawn);
+    assert_eq!(pawn.color, PieceColor::White);
+    assert!(game.history().pocket(PieceColor::White).is_empty());
+    assert!(game.history().turns[2].is_drop());
+    assert_eq!(san_moves(game.history())[2], "P@e4");
+    assert_eq!(game.turn(), PieceColor::Black);
+}
+
+#[test]
+fn crazyhouse_pawns_cannot_be_dropped_on_last_rank() {
+    let mut game = variant_from_fen(GameVariant::Crazyhouse, "4k3/8/8/8/8/8/8/4K3[P] w - - 0 1");
+    assert_eq!(game.history().pocket(PieceColor::White), [PieceType::Pawn]);
+    game.drop_piece(PieceType::Pawn, "a8");
+    assert!(game.history().turns.is_empty());
+    game.drop_piece(PieceType::Pawn, "a7");
+    assert_eq!(game.history().turns.len(), 1);
+}
+
+#[test]
+fn crazyhouse_promoted_pieces_go_back_in_hand_as_pawns() {
+    let mut game = variant_from_fen(GameVariant::Crazyhouse, "4k3/1P6/8/8/8/8/1r6/4K3 w - - 0 1");
+    game.promote("b7", "b8", PieceType::Queen);
+    assert!(game.piece_at("b8").unwrap().promoted);
+    game.play("b2", "b8");
+    let turn = game.history().turns[1];
+    assert_eq!(turn.captured, Some(PieceType::Queen));
+    assert_eq!(game.history().pocket(PieceColor::Black), [PieceType::Pawn]);
+    game.play("e1", "d1");
+    game.drop_piece(PieceType::Pawn, "b5");
+    let pawn = game.piece_at("b5").expect("pawn wasn't dropped");
+    assert_eq!(pawn.color, PieceColor::Black);
+    assert!(game.history().pocket(PieceColor::Black).is_empty());
+}
+
+#[test]
+fn promoted_pieces_are_marked_in_fen() {
+    let fen = "1Q~2k3/8/8/8/8/8/8/4K3 w - - 0 1";
+    let position = parse_fen(fen).unwrap();
+    assert!(position.pieces.iter().any(|piece| piece.promoted));
+    assert_eq!(to_fen(&position), fen);
+    assert!(parse_fen("~3k3/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+}
+
+#[test]
+fn drops_can_be_typed() {
+    let mut game = variant_from_fen(GameVariant::Crazyhouse, "4k3/8/8/8/8/8/8/4K3[Nn] w - - 0 1");
+    type_move(&mut game, "N@f3");
+    assert_eq!(game.piece_at("f3").unwrap().piece_type, PieceType::Knight);
+    type_move(&mut game, "N@c6");
+    let knight = game.piece_at("c6").expect("knight wasn't dropped");
+    assert_eq!(knight.color, PieceColor::Black);
+    assert!(game.history().turns.iter().all(|turn| turn.is_drop()));
+}
+
+#[test]
+fn fog_hides_what_the_pieces_cannot_reach() {
+    let mut game = variant_from_fen(GameVariant::FogOfWar, "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+    game.update();
+    let fog = game.resource::<Fog>();
+    assert_eq!(fog.viewer, Some(PieceColor::White));
+    assert!(!fog.hides(square("e4")));
+    assert!(!fog.hides(square("d2")));
+    assert!(fog.hides(square("e5")));
+    assert!(fog.hides(square("e8")));
+}
+
+#[test]
+fn fog_hands_the_board_over_in_hot_seat_games() {
+    let mut game = variant_from_fen(GameVariant::FogOfWar, "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+    game.update();
+    game.resource_mut::<Fog>().handoff = false;
+    game.play("e2", "e4");
+    game.update();
+    let fog = game.resource::<Fog>();
+    assert_eq!(fog.viewer, Some(PieceColor::Black));
+    assert!(fog.handoff);
+    assert!(fog.hides(square("e4")));
+    assert!(!fog.hides(square("d7")));
+}
+
+#[test]
+fn antichess_captures_are_compulsory() {
+    let mut game = variant_from_fen(GameVariant::Antichess, "4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1");
+    game.play("e1", "e2");
+    assert!(game.history().turns.is_empty());
+    game.play("e4", "d5");
+    assert_eq!(game.piece_at("d5").unwrap().color, PieceColor::White);
+    assert_eq!(game.turn(), PieceColor::Black);
+}
+
+#[test]
+fn antichess_is_won_by_losing_every_piece() {
+    let mut game = variant_from_fen(GameVariant::Antichess, "4k3/8/8/8/8/8/1p6/R7 b - - 0 1");
+    game.play("b2", "a1");
+    assert_eq!(game.piece_at("a1").unwrap().piece_type, PieceType::Queen);
+    let result = run_to_result(&mut game);
+    assert_eq!(result.winner, Some(PieceColor::White));
+    assert_eq!(result.reason, EndReason::PiecesGone);
+}
+
+#[test]
+fn antichess_pawns_promote_to_king() {
+    let mut game = variant_from_fen(GameVariant::Antichess, "8/P7/8/8/8/8/8/7k w - - 0 1");
+    game.promote("a7", "a8", PieceType::King);
+    assert_eq!(game.piece_at("a8").unwrap().piece_type, PieceType::King);
+    assert_eq!(san_moves(game.history()), vec!["a8=K"]);
+    assert_eq!(game.history().turns[0].uci(), "a7a8k");
+}
+
+#[test]
+fn pawns_promote_to_queen_unless_asked_otherwise() {
+    let mut game = variant_from_fen(GameVariant::Standard, "8/P7/4k3/8/8/8/7p/4K3 w - - 0 1");
+    game.play("a7", "a8");
+    assert_eq!(game.piece_at("a8").unwrap().piece_type, PieceType::Queen);
+    game.promote("h2", "h1", PieceType::Knight);
+    assert_eq!(game.piece_at("h1").unwrap().piece_type, PieceType::Knight);
+    assert_eq!(san_moves(game.history()), vec!["a8=Q", "h1=N"]);
+    let history = game.history().clone();
+    let replayed = history.position_after(2);
+    assert!(replayed
+        .iter()
+        .any(|piece| (piece.x, piece.y) == square("h1") && piece.piece_type == PieceType::Knight));
+}
+
+#[test]
+fn only_antichess_promotes_to_king() {
+    let mut game = variant_from_fen(GameVariant::Standard, "4k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+    game.promote("a7", "a8", PieceType::King);
+    assert_eq!(game.piece_at("a8").unwrap().piece_type, PieceType::Queen);
+}
+
+#[test]
+fn promotion_waits_for_the_piece_without_auto_queen() {
+    let mut game = variant_from_fen(GameVariant::Standard, "4k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+    game.resource_mut::<PendingPromotion>().ask = true;
+    game.play("a7", "a8");
+    assert!(game.history().turns.is_empty());
+    assert_eq!(game.piece_at("a7").unwrap().piece_type, PieceType::Pawn);
+    let event = game
+        .resource::<PendingPromotion>()
+        .event
+        .expect("no promotion waiting");
+    game.send(MovePieceEvent {
+        promotion: Some(PieceType::Rook),
+        ..event
+    });
+    game.update();
+    assert_eq!(game.piece_at("a8").unwrap().piece_type, PieceType::Rook);
+    assert!(game.resource::<PendingPromotion>().event.is_none());
+}
+
+#[test]
+fn settings_save_only_what_was_changed_in_the_menu() {
+    let mut game = TestGame::new();
+    let path = game.resource::<ConfigPath>().0.clone();
+    // As if given on the command line
+    game.resource_mut::<Config>().theme = "Marble".to_string();
+    game.open_settings();
+    game.resource_mut::<Config>().game.auto_queen = false;
+    game.close_settings();
+    let saved = read_config(&path).expect("settings weren't saved");
+    assert!(!saved.game.auto_queen);
+    assert_eq!(saved.theme, Config::default().theme);
+}
+
+#[test]
+fn horde_spawns_every_pawn() {
+    let mut game = TestGame::with_setup(GameSetup {
+        variant: GameVariant::Horde,
+        ..Default::default()
+    });
+    let pieces = game.pieces();
+    assert_eq!(pieces.len(), 52);
+    let horde: Vec<_> = pieces
+        .iter()
+        .filter(|piece| piece.color == PieceColor::White)
+        .collect();
+    assert_eq!(horde.len(), 36);
+    assert!(horde
+        .iter()
+        .all(|piece| piece.piece_type == PieceType::Pawn));
+    assert!(game.captured().by(PieceColor::Black).is_empty());
+    assert!(game.captured().by(PieceColor::White).is_empty());
+}
+
+#[test]
+fn blocked_pawns_cannot_double_step() {
+    let mut game = TestGame::from_fen("4k3/8/8/8/8/4n3/4P3/4K3 w - - 0 1");
+    game.play("e2", "e4");
+    assert!(game.history().turns.is_empty());
+    assert!(game.piece_at("e4").is_none());
+    game.play("e1", "d2");
+    game.play("e3", "f5");
+    game.play("e2", "e4");
+    assert_eq!(game.piece_at("e4").unwrap().piece_type, PieceType::Pawn);
+}
+
+#[test]
+fn horde_pawns_double_step_from_first_rank() {
+    let mut game = variant_from_fen(GameVariant::Horde, "4k3/8/8/8/8/8/1P6/PP6 w - - 0 1");
+    game.play("b1", "b3");
+    assert!(game.history().turns.is_empty());
+    game.play("a1", "a3");
+    assert_eq!(game.piece_at("a3").unwrap().piece_type, PieceType::Pawn);
+}
+
+#[test]
+fn horde_is_lost_when_every_pawn_is_taken() {
+    let mut game = variant_from_fen(GameVariant::Horde, "4k3/8/8/8/8/8/8/Pr6 b - - 0 1");
+    game.play("b1", "a1");
+    let result = run_to_result(&mut game);
+    assert_eq!(result.winner, Some(PieceColor::Black));
+    assert_eq!(result.reason, EndReason::HordeTaken);
+}
+
+#[test]
+fn ai_takes_a_free_piece() {
+    let position = parse_fen("4k3/8/8/8/3r4/8/8/3QK3 w - - 0 1").unwrap();
+    let best = ai::best_move(&position.pieces, &History::default(), PieceColor::White, 2);
+    assert_eq!(best, Some((square("d1"), square("d4"))));
+}
+
+#[test]
+fn black_moves_are_numbered_again_after_a_comment() {
+    let mut game = TestGame::new();
+    game.play("e2", "e4");
+    game.play("e7", "e5");
+    let mut annotations = Annotations::default();
+    annotations.0.insert(
+        1,
+        PositionAnnotations {
+            marks: vec![(square("e4"), AnnotationColor::Green)],
+            ..Default::default()
+        },
+    );
+    let pgn = export_pgn(game.history(), &GameSetup::default(), None, &annotations);
+    assert!(pgn.contains("1. e4 {[%csl Ge4]} 1... e5"), "{}", pgn);
+}
+
+#[test]
+fn time_control_increment_is_added_after_a_move() {
+    let mut game = TestGame::with_setup(GameSetup {
+        time_control: Some(TimeControl::new(1, 5)),
+        ..Default::default()
+    });
+    game.play("e2", "e4");
+    let clocks = game.resource::<Clocks>();
+    assert!(clocks.white > Duration::from_secs(60));
+    assert!(clocks.black <= Duration::from_secs(60));
+}
+
+#[test]
+fn clocks_keep_running_in_the_settings() {
+    let mut game = TestGame::with_setup(GameSetup {
+        time_control: Some(TimeControl::new(1, 0)),
+        ..Default::default()
+    });
+    game.resource_mut::<Clocks>().white = Duration::from_millis(1);
+    game.open_settings();
+    thread::sleep(Duration::from_millis(10));
+    game.update();
+    game.update();
+    assert_eq!(game.state(), AppState::GameOver);
+    assert_eq!(game.result().unwrap().reason, EndReason::Timeout);
+}
+
+#[test]
+fn network_messages_are_parsed_back() {
+    let messages = vec![
+        Message::Variant(GameVariant::KingOfTheHill),
+        Message::Position(None),
+        Message::Position(Some("4k3/8/8/8/8/8/8/4K3 w - - 0 1".to_string())),
+        Message::Color(PieceColor::Black),
+        Message::Move("e2e4".to_string()),
+    ];
+    for message in messages {
+        assert_eq!(Message::parse(&message.to_string()), Some(message));
+    }
+    assert_eq!(Message::parse("move"), None);
+    assert_eq!(Message::parse("color red"), None);
+    assert_eq!(Message::parse("move e2e4 e7e5"), None);
+    assert_eq!(Message::parse("hello"), None);
+    assert_eq!(Message::parse("variant Bughouse"), None);
+}
+
+#[test]
+fn leaving_a_network_game_closes_the_connection_and_frees_the_port() {
+    let address = "127.0.0.1:47878";
+    let mut host = Peer::host(address);
+    let opponent = loop {
+        match TcpStream::connect(address) {
+            Ok(stream) => break stream,
+            // The host binds the port from its own thread
+            Err(_) => thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    host.send(Message::Position(None));
+    opponent
+        .set_read_timeout(Some(Duration::from_millis(10)))
+        .unwrap();
+    let mut reader = BufReader::new(opponent);
+    let mut line = String::new();
+    // Sent once the host has seen the connection
+    while reader.read_line(&mut line).is_err() {
+        host.poll();
+    }
+    assert_eq!(line.trim(), "position startpos");
+    drop(host);
+    reader.get_ref().set_read_timeout(None).unwrap();
+    line.clear();
+    assert_eq!(reader.read_line(&mut line).unwrap(), 0);
+
+    let waiting = Peer::host(address);
+    thread::sleep(Duration::from_millis(100));
+    drop(waiting);
+    thread::sleep(Duration::from_millis(300));
+    assert!(TcpListener::bind(address).is_ok(), "port is still bound");
+}
+
+#[test]
+fn pgn_loaded_from_the_command_line_sets_up_its_variant() {
+    let path = env::temp_dir().join(format!("bevy_chess_test_{}.pgn", process::id()));
+    fs::write(
+        &path,
+        "[Variant \"Crazyhouse\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\n1. e4 *\n",
+    )
+    .unwrap();
+    let launch = |args: &[&str]| {
+        let args = Args::from_iter(
+            ["bevy_chess", "--load", path.to_str().unwrap()]
+                .iter()
+                .chain(args),
+        );
+        args.launch(&mut GameSetup::default())
+    };
+    let setup = match launch(&[]) {
+        Ok(Some(Launch::Load(save))) => save.setup,
+        _ => panic!("PGN wasn't loaded"),
+    };
+    assert_eq!(setup.variant, GameVariant::Crazyhouse);
+    assert_eq!(
+        setup.fen.as_deref(),
+        Some("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1")
+    );
+    assert!(launch(&["--variant", "standard"]).is_err());
+    assert!(launch(&["--variant", "crazyhouse"]).is_ok());
+    fs::remove_file(&path).unwrap();
+
+    assert!(Args::from_iter_safe(&["bevy_chess", "--time-control", "abc"]).is_err());
+}